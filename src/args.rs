@@ -0,0 +1,183 @@
+use std::env;
+use std::fs;
+use std::path::{Path,PathBuf};
+use crate::header::{XyOrigin, ZOrigin};
+
+/// command line arguments: any number of toolpath files, directories or globs, followed by any flags.
+/// `diff OLD NEW` compares two revisions of a program instead, `import PROFILE` reads the machine's limits into the config,
+/// and `--watch DIR` validates files as they're written
+#[derive(Clone,Debug,Default)]
+pub struct Args {
+    pub paths: Vec<PathBuf>,
+    pub material: Option<String>,
+    pub ledger: Option<PathBuf>,
+    pub thickness: Option<f32>,
+    pub z_origin: Option<ZOrigin>,
+    pub xy_origin: Option<XyOrigin>,
+    pub reference: Option<PathBuf>,
+    pub diff: Option<(PathBuf,PathBuf)>,
+    pub import: Option<PathBuf>,
+    pub svg: Option<PathBuf>,
+    pub watch: Option<PathBuf>,
+    pub html: Option<PathBuf>,
+    pub machine: Option<String>,
+    pub config: Option<PathBuf>,
+}
+
+pub fn parse() -> Result<Args,String> {
+    let mut args = Args::default();
+    let mut iter = env::args().skip(1).peekable();
+    let command = iter.next_if(|a| a == "diff" || a == "import");
+    let diff = command.as_deref() == Some("diff");
+    let import = command.as_deref() == Some("import");
+    let mut paths: Vec<PathBuf> = Vec::new();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--material" => {
+                let material = iter.next().ok_or("--material needs a material name")?;
+                args.material = Some(material.to_lowercase());
+            }
+            "--ledger" => {
+                let ledger = iter.next().ok_or("--ledger needs a file")?;
+                args.ledger = Some(PathBuf::from(ledger));
+            }
+            "--thickness" => {
+                let thickness = iter.next().ok_or("--thickness needs a number")?;
+                args.thickness = Some(thickness.parse::<f32>().map_err(|_| format!("invalid thickness '{}'", thickness))?);
+            }
+            "--z-origin" => {
+                args.z_origin = Some(match iter.next().as_deref() {
+                    Some("top") => ZOrigin::Top,
+                    Some("bottom") => ZOrigin::Bottom,
+                    _ => return Err("--z-origin needs 'top' or 'bottom'".into()),
+                });
+            }
+            "--xy-origin" => {
+                args.xy_origin = Some(match iter.next().as_deref() {
+                    Some("corner") => XyOrigin::Corner,
+                    Some("center") => XyOrigin::Center,
+                    _ => return Err("--xy-origin needs 'corner' or 'center'".into()),
+                });
+            }
+            "--reference" => {
+                let reference = iter.next().ok_or("--reference needs a .dxf or .svg file")?;
+                args.reference = Some(PathBuf::from(reference));
+            }
+            "--svg" => {
+                let svg = iter.next().ok_or("--svg needs a file to write the plot to")?;
+                args.svg = Some(PathBuf::from(svg));
+            }
+            "--watch" => {
+                let watch = iter.next().ok_or("--watch needs a directory")?;
+                args.watch = Some(PathBuf::from(watch));
+            }
+            "--html" => {
+                let html = iter.next().ok_or("--html needs a file to write the report to")?;
+                args.html = Some(PathBuf::from(html));
+            }
+            "--machine" => {
+                let machine = iter.next().ok_or("--machine needs a machine profile from config.toml")?;
+                args.machine = Some(machine);
+            }
+            "--config" => {
+                let config = iter.next().ok_or("--config needs a config file")?;
+                args.config = Some(PathBuf::from(config));
+            }
+            flag if flag.starts_with("--") => {
+                return Err(format!("unknown option '{}'", flag));
+            }
+            path => {
+                if (diff && paths.len() == 2) || (import && paths.len() == 1) {
+                    return Err(format!("unexpected argument '{}'", path));
+                }
+                paths.push(path.parse::<PathBuf>().map_err(|_| "no such path")?);
+            }
+        }
+    }
+    if diff {
+        match <[PathBuf;2]>::try_from(paths) {
+            Ok([old, new]) => args.diff = Some((old, new)),
+            Err(_) => return Err("diff needs an old and a new toolpath file".into()),
+        }
+    } else if import {
+        args.import = Some(paths.pop().ok_or("import needs a mach3 .xml or linuxcnc .ini machine profile")?);
+    } else {
+        args.paths = paths;
+    }
+    Ok(args)
+}
+
+/// `*` matches any run of characters and `?` any one character
+fn matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| matches(rest, &name[i..])),
+        Some((c, rest)) => name.split_first().is_some_and(|(n, name)| (*c == '?' || c == n) && matches(rest, name)),
+    }
+}
+
+fn is_toolpath(path: &Path) -> bool {
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+    path.is_file() && path.file_name().is_some_and(|n| n != "config.txt")
+        && matches!(extension.as_deref(), Some("txt" | "tap" | "nc" | "ngc" | "gcode"))
+}
+
+/// turns directories into the toolpath files inside them, and globs in file names into the files they match
+pub fn expand(paths: &[PathBuf]) -> Result<Vec<PathBuf>,String> {
+    let mut expanded: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let listing = if path.is_dir() {
+            Some((path.as_path(), "*".to_string()))
+        } else if name.contains(['*', '?']) {
+            Some((path.parent().unwrap_or(Path::new("")), name))
+        } else {
+            None
+        };
+        let Some((dir, pattern)) = listing else {
+            expanded.push(path.clone());
+            continue;
+        };
+        let found = toolpaths(dir, &pattern)?;
+        if found.is_empty() {
+            return Err(format!("no toolpath files match '{}'",path.display()));
+        }
+        expanded.extend(found);
+    }
+    Ok(expanded)
+}
+
+/// the toolpath files in a directory whose names match a glob, sorted by name
+pub fn toolpaths(dir: &Path, pattern: &str) -> Result<Vec<PathBuf>,String> {
+    //an empty directory is the current one, and the files found in it are left without a ./ in front
+    let entries = fs::read_dir(if dir.as_os_str().is_empty() { Path::new(".") } else { dir })
+        .map_err(|_| format!("couldn't read directory '{}'",dir.display()))?;
+    let pattern: Vec<char> = pattern.chars().collect();
+    let mut found: Vec<PathBuf> = entries.filter_map(|e| e.ok().map(|e| dir.join(e.file_name())))
+        .filter(|p| is_toolpath(p))
+        .filter(|p| p.file_name().is_some_and(|n| matches(&pattern, &n.to_string_lossy().chars().collect::<Vec<char>>())))
+        .collect();
+    found.sort();
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directories_and_globs_expand_to_the_toolpaths_they_hold() {
+        let dir = env::temp_dir().join(format!("validate_toolpath_expand_{}",std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["a.nc", "b.TXT", "ab.nc", "config.txt", "notes.md"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let all = expand(std::slice::from_ref(&dir));
+        let glob = expand(&[dir.join("?.nc"), dir.join("b.TXT")]);
+        let none = expand(&[dir.join("*.ngc")]);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(all.unwrap(), vec![dir.join("a.nc"), dir.join("ab.nc"), dir.join("b.TXT")]);
+        assert_eq!(glob.unwrap(), vec![dir.join("a.nc"), dir.join("b.TXT")]);
+        assert_eq!(none, Err(format!("no toolpath files match '{}'",dir.join("*.ngc").display())));
+    }
+}
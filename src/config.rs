@@ -1,6 +1,11 @@
 use regex::Regex;
+use std::env;
+use std::fmt;
 use std::fs;
 use std::collections::HashMap;
+use std::path::{Path,PathBuf};
+use crate::args::Args;
+use crate::{Outcome, Status, CHECKS};
 use std::fs::File;
 use std::io::prelude::*;
 
@@ -9,58 +14,870 @@ lazy_static! {
 }
 
 macro_rules! config_items {
-    ( $( ($name:literal, $default:literal $(,$desc:literal)?), )* ) => {{
-        lazy_static! {
-            static ref DEFAULT_CONFIG: String = [
-                $( $("\n\r//",$desc,)?"\n\r//default: ",stringify!($default),"\n\r",$name," = ",stringify!($default),"\n\n",)*
-            ].concat();
-        }
-        (
-            HashMap::from([
-                $(($name.into(),$default),)*
-            ]),
-            &DEFAULT_CONFIG
-        )
-    }}
-}
-
-pub fn read_config() -> HashMap<String,f32> {
-    let (mut config_items,default_config) = config_items!(
-        ("MACHINE_SIZE_X", 24.0, "width of machine area in inches"),
-        ("MACHINE_SIZE_Y", 48.0, "depth of machine area in inches"),
+    ( $( ($name:literal, $default:literal $(,$desc:literal)?), )* ) => {
+        vec![ $( ($name, $default, concat!("" $(,$desc)?)), )* ]
+    }
+}
+
+/// a config item with its default value, and a description for the default config if it has one
+type Item = (&'static str, f32, &'static str);
+
+fn items() -> Vec<Item> {
+    config_items!(
+        ("SOFT_MIN_X", -1.0, "soft limits of each axis in machine coordinates, in inches. failure if any move goes past them.\nthe default minimums leave room for lead-ins and the tool's radius around stock zeroed on its southwest corner.\nthese, velocities and accelerations can be read from a mach3 .xml or linuxcnc .ini profile with `validate_toolpath import FILE`"),
+        ("SOFT_MAX_X", 24.0),
+        ("SOFT_MIN_Y", -1.0),
+        ("SOFT_MAX_Y", 48.0),
+        ("SOFT_MIN_Z", -4.0),
+        ("SOFT_MAX_Z", 4.0),
+        ("WORK_ORIGIN_X", 0.0, "where the southwest corner of the stock, on the table, is in machine coordinates"),
+        ("WORK_ORIGIN_Y", 0.0),
+        ("WORK_ORIGIN_Z", 0.0),
         ("MIN_PASSES", 2.0, "warning if there are less than or equal to this number of passes"),
         ("MAX_PASSES", 6.0, "warning if there are greater than or equal to this number of passes"),
         ("DEPTH_THRESHOLD", 0.0625, "the maximum amount the endmill should be allowed to cut into the table"),
         ("MIN_OFFSET", -0.2, "fail offset check if southwest part corner is further southwest than this"),
         ("MAX_OFFSET", 0.75, "fail offset check if southwest part corner is further northeast than this"),
         ("WARN_SAFE_HEIGHT", 0.15, "warning if min traversal height is lower than this"),
-        ("FAIL_SAFE_HEIGHT", 0.1, "failure if min traversal height is lower than this"),
+        ("FAIL_SAFE_HEIGHT", 0.1, "failure if min traversal height is lower than this. the tool also has to be at least this far above\nthe stock before the first rapid, and at the end of the program"),
         ("PASS_FREQUENCY_THRESHOLD", 20.0, "require at least this many lines of g-code in each pass"),
-    );
-
-    if let Ok(contents) = fs::read_to_string("./config.txt") {
-        for l in contents.lines() {
-            if !(l.starts_with("//") || l.starts_with("#")) {
-                l.split_terminator(&[' ', '=', ':'][..]).nth(0).map(|k| {
-                    let k = k.trim();
-                    let value = config_items.get_mut(k);
-                    if let Some(v) = value {
-                        if let Some(new) = NUM_RE.find(l) {
-                            *v = new.as_str().parse::<f32>().unwrap();
-                        }
-                    }
-                });
+        ("MAX_MACHINE_FEED", 300.0, "warning if any feed rate is faster than the machine can move, in inches per minute"),
+        ("MAX_CUT_FEED", 150.0, "warning if a lateral cutting feed is faster than this. like all feed limits, it can be set for a\nmaterial, tool number or tool diameter in [materials.aluminum], [tools.T2] or [tools.\"0.25\"] sections"),
+        ("MAX_CUT_FEED[aluminum]", 60.0),
+        ("MAX_CUT_FEED[polycarbonate]", 120.0),
+        ("MAX_CUT_FEED[plywood]", 150.0),
+        ("MAX_CUT_FEED[hdpe]", 150.0),
+        ("MAX_PLUNGE_FEED", 30.0, "warning if a plunging (z only) feed is faster than this"),
+        ("MAX_PLUNGE_FEED[aluminum]", 10.0),
+        ("MAX_PLUNGE_FEED[polycarbonate]", 20.0),
+        ("MAX_PLUNGE_FEED[plywood]", 40.0),
+        ("MAX_PLUNGE_FEED[hdpe]", 30.0),
+        ("MIN_SPINDLE_RPM", 5000.0, "warning if the spindle speed is set lower than the machine can run"),
+        ("MAX_SPINDLE_RPM", 24000.0, "warning if the spindle speed is set higher than the machine can run"),
+        ("FLUTES", 2.0, "flute count for end mills that don't list it in the tool name, e.g. flutes = 1 under [tools.T3]"),
+        ("MIN_CHIP_LOAD", 0.001, "warning if the chip load (inches per tooth) is lower than this, because the tool will rub"),
+        ("MIN_CHIP_LOAD[aluminum]", 0.001),
+        ("MIN_CHIP_LOAD[polycarbonate]", 0.003),
+        ("MIN_CHIP_LOAD[plywood]", 0.004),
+        ("MIN_CHIP_LOAD[hdpe]", 0.004),
+        ("MAX_CHIP_LOAD", 0.015, "warning if the chip load (inches per tooth) is higher than this, because the tool is overloaded"),
+        ("MAX_CHIP_LOAD[aluminum]", 0.004),
+        ("MAX_CHIP_LOAD[polycarbonate]", 0.010),
+        ("MAX_CHIP_LOAD[plywood]", 0.012),
+        ("MAX_CHIP_LOAD[hdpe]", 0.012),
+        ("MIN_SURFACE_SPEED", 100.0, "warning if the surface speed (feet per minute) is lower than this"),
+        ("MIN_SURFACE_SPEED[aluminum]", 400.0),
+        ("MAX_SURFACE_SPEED", 3000.0, "warning if the surface speed (feet per minute) is higher than this"),
+        ("MAX_SURFACE_SPEED[aluminum]", 1500.0),
+        ("MAX_STEPDOWN", 1.0, "warning if the stepdown between passes is more than this times the tool diameter"),
+        ("MAX_STEPDOWN[aluminum]", 0.5),
+        ("MAX_STEPOVER", 0.5, "warning if the stepover between pocketing passes is more than this times the tool diameter"),
+        ("MAX_STEPOVER[aluminum]", 0.4),
+        ("MAX_SLOT_DEPTH", 0.5, "warning if a pocket cuts a full width slot deeper than this times the tool diameter"),
+        ("CUT_DIRECTION", 0.0, "warning if a profile is not cut in this direction. 1 for climb, -1 for conventional, 0 for either"),
+        ("CUT_DIRECTION[polycarbonate]", 1.0),
+        ("VELOCITY_X", 300.0, "fastest the x axis can move, in inches per minute. used for rapids in the run time estimate"),
+        ("VELOCITY_Y", 300.0, "fastest the y axis can move, in inches per minute"),
+        ("VELOCITY_Z", 100.0, "fastest the z axis can move, in inches per minute"),
+        ("ACCELERATION_X", 10.0, "acceleration of the x axis, in inches per second squared"),
+        ("ACCELERATION_Y", 10.0, "acceleration of the y axis, in inches per second squared"),
+        ("ACCELERATION_Z", 5.0, "acceleration of the z axis, in inches per second squared"),
+        ("JUNCTION_DEVIATION", 0.002, "how far the machine is allowed to round off corners to keep moving, in inches"),
+        ("TOOL_CHANGE_TIME", 60.0, "time taken by each manual tool change, in seconds"),
+        ("MAX_JOB_TIME", 45.0, "warning if the estimated run time is longer than this, in minutes"),
+        ("TOOL_LIFE", 20000.0, "warning if a tool has cut more than this many inches, e.g. tool_life = 5000 under [tools.\"0.125\"]"),
+        ("TOOL_SERIAL", 0.0, "serial number of the bit in each tool slot, e.g. tool_serial = 17 under [tools.T1], for keeping track of\ntool wear across programs with --ledger. 0 means the bit isn't tracked"),
+        ("MATERIAL_THICKNESS", 0.0, "material thickness to use if the toolpath header doesn't say. 0 means always use the header"),
+        ("MAX_TAB_LENGTH", 0.75, "raised parts of a through cut no longer than this are treated as holding tabs, in inches"),
+        ("REFERENCE_TOLERANCE", 0.01, "how far the cut part can be from the --reference drawing, in inches"),
+        ("DIFF_TOLERANCE", 0.05, "how far cutting paths can move between revisions before diff reports them, in inches"),
+    )
+}
+
+/// which section of config.toml each item goes in. items in `machine` can also be set per machine profile,
+/// and all the others per material or tool
+const SECTIONS: [(&str, &[&str]); 14] = [
+    ("machine", &["SOFT_MIN_X", "SOFT_MAX_X", "SOFT_MIN_Y", "SOFT_MAX_Y", "SOFT_MIN_Z", "SOFT_MAX_Z",
+        "WORK_ORIGIN_X", "WORK_ORIGIN_Y", "WORK_ORIGIN_Z", "MAX_MACHINE_FEED", "MIN_SPINDLE_RPM", "MAX_SPINDLE_RPM",
+        "VELOCITY_X", "VELOCITY_Y", "VELOCITY_Z", "ACCELERATION_X", "ACCELERATION_Y", "ACCELERATION_Z",
+        "JUNCTION_DEVIATION", "TOOL_CHANGE_TIME"]),
+    ("checks.safe_height", &["WARN_SAFE_HEIGHT", "FAIL_SAFE_HEIGHT"]),
+    ("checks.depth", &["DEPTH_THRESHOLD", "MATERIAL_THICKNESS"]),
+    ("checks.offset", &["MIN_OFFSET", "MAX_OFFSET"]),
+    ("checks.passes", &["MIN_PASSES", "MAX_PASSES", "PASS_FREQUENCY_THRESHOLD", "MAX_STEPDOWN"]),
+    ("checks.stepover", &["MAX_STEPOVER", "MAX_SLOT_DEPTH"]),
+    ("checks.through_cut", &["MAX_TAB_LENGTH"]),
+    ("checks.cut_direction", &["CUT_DIRECTION"]),
+    ("checks.tool_wear", &["TOOL_LIFE", "TOOL_SERIAL"]),
+    ("checks.run_time", &["MAX_JOB_TIME"]),
+    ("checks.feed_rate", &["MAX_CUT_FEED", "MAX_PLUNGE_FEED"]),
+    ("checks.chip_load", &["FLUTES", "MIN_CHIP_LOAD", "MAX_CHIP_LOAD", "MIN_SURFACE_SPEED", "MAX_SURFACE_SPEED"]),
+    ("checks.reference", &["REFERENCE_TOLERANCE"]),
+    ("checks.diff", &["DIFF_TOLERANCE"]),
+];
+
+/// old names for items, which still work. the machine size was the only limit before soft limits
+const RENAMED: [(&str, &str); 2] = [
+    ("MACHINE_SIZE_X", "SOFT_MAX_X"),
+    ("MACHINE_SIZE_Y", "SOFT_MAX_Y"),
+];
+
+/// the current name for an item, warning about where an old one was used
+fn current_name(name: String, place: &str) -> String {
+    match RENAMED.iter().find(|(old, _)| *old == name) {
+        Some((old, new)) => {
+            eprintln!("Warning: {}: {} has been renamed to {}",place,old.to_lowercase(),new.to_lowercase());
+            new.to_string()
+        }
+        None => name,
+    }
+}
+
+/// items that only make sense as whole numbers
+const WHOLE_NUMBERS: [&str; 5] = ["MIN_PASSES", "MAX_PASSES", "PASS_FREQUENCY_THRESHOLD", "FLUTES", "TOOL_SERIAL"];
+
+/// items that can be set with a word instead of a number
+const NAMED_VALUES: [(&str, &str, f32); 3] = [
+    ("CUT_DIRECTION", "climb", 1.0),
+    ("CUT_DIRECTION", "conventional", -1.0),
+    ("CUT_DIRECTION", "either", 0.0),
+];
+
+/// how much a problem found by a check counts
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum Severity {
+    Off,
+    Warning,
+    Fail,
+}
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Off => write!(f,"off"),
+            Severity::Warning => write!(f,"warning"),
+            Severity::Fail => write!(f,"fail"),
+        }
+    }
+}
+
+/// keys in any `[checks.*]` section: `severity` for everything the check finds,
+/// or `warnings` and `failures` for just the problems it would otherwise warn or fail for.
+/// the kinds of issue listed for the check in `CHECKS` are severity keys too
+const SEVERITY_KEYS: [&str; 3] = ["severity", "warnings", "failures"];
+
+fn parse_severity(key: &str, text: &str) -> Result<Severity,String> {
+    match unquote(text).to_lowercase().as_str() {
+        "off" => Ok(Severity::Off),
+        "warning" => Ok(Severity::Warning),
+        "fail" => Ok(Severity::Fail),
+        _ => Err(format!("{} needs one of \"off\", \"warning\", \"fail\"",key)),
+    }
+}
+
+/// config values, and where each one came from if it was set somewhere
+#[derive(Clone)]
+pub struct Config {
+    pub items: HashMap<String,f32>,
+    /// e.g. `'config.toml'`, or `(validate:) at line 3` for directives in the program
+    pub sources: HashMap<String,String>,
+    /// every config file that was read, lowest priority first
+    pub files: Vec<PathBuf>,
+    /// check id, severity key, severity and where it was set, with later ones winning
+    severities: Vec<(&'static str,&'static str,Severity,String)>,
+    /// every `[machines.NAME]` section
+    profiles: Vec<String>,
+    machine_values: Vec<(String,&'static str,f32,PathBuf)>,
+}
+
+impl Config {
+    fn set(&mut self, name: String, value: f32, file: &Path) {
+        self.items.insert(name.clone(), value);
+        self.sources.insert(name, format!("'{}'",file.display()));
+    }
+
+    /// sets an item from somewhere other than a config file, like `DEPTH_THRESHOLD=0.1` in a program
+    pub fn set_from(&mut self, name: &str, value: &str, source: String) -> Result<(),String> {
+        let (base, qualifiers) = name.trim().split_at(name.trim().find('[').unwrap_or(name.trim().len()));
+        let base = current_name(base.to_uppercase(), &source);
+        if !self.items.contains_key(&base) {
+            return Err(format!("unknown config item '{}'",name.trim()));
+        }
+        //words like climb don't need quotes here
+        let value = match value.trim() {
+            word if word.chars().all(|c| c.is_ascii_alphabetic()) => parse_value(&base, &format!("\"{}\"",word))?,
+            value => parse_value(&base, value)?,
+        };
+        let name = format!("{}{}",base,qualifiers);
+        self.items.insert(name.clone(), value);
+        self.sources.insert(name, source);
+        Ok(())
+    }
+
+    fn use_machine(&mut self, machine: &str) -> Result<(),String> {
+        if !self.profiles.iter().any(|m| m == machine) {
+            return Err(format!("no config file has a [machines.{}] profile{}",machine,
+                if self.profiles.is_empty() { String::new() } else { format!(", there's {}",self.profiles.join(", ")) }
+            ));
+        }
+        //the chosen machine profile wins over [machine] in every file
+        let values: Vec<(&'static str,f32,PathBuf)> = self.machine_values.iter()
+            .filter(|(m,..)| m == machine).map(|(_,name,value,file)| (*name,*value,file.clone())).collect();
+        for (name, value, file) in values {
+            self.set(name.into(), value, &file);
+        }
+        Ok(())
+    }
+
+    /// changes how much each issue an outcome found counts, by the severities set for its check.
+    /// a setting for the kind of issue wins over one for just warnings or failures, which wins over the one for the whole check
+    pub fn apply_severities(&self, outcomes: &mut [Outcome]) {
+        for outcome in outcomes.iter_mut() {
+            let Some((id, ..)) = CHECKS.iter().find(|(_, name, _)| *name == outcome.name) else { continue };
+            if !matches!(outcome.status, Status::Warning | Status::Fail) { continue }
+            let statuses = outcome.issue_statuses();
+            let kinds = outcome.issue_kinds();
+            let changes: Vec<Option<(Status,String)>> = statuses.iter().zip(&kinds).map(|(status, kind)| {
+                let level = match status { Status::Warning => "warnings", Status::Fail => "failures", _ => return None };
+                let keys = kind.iter().copied().chain([level, "severity"]);
+                let (_, key, severity, source) = keys.into_iter().find_map(|k| self.severities.iter().rev().find(|(c, key, ..)| c == id && *key == k))?;
+                let changed = match severity {
+                    Severity::Off => Status::Suppressed,
+                    Severity::Warning => Status::Warning,
+                    Severity::Fail => Status::Fail,
+                };
+                let change = if changed == Status::Suppressed { "turned off".into() } else { format!("counted as {}",changed) };
+                (changed != *status).then(|| (changed, format!("{} by {} = \"{}\" from {}",change,key,severity,source)))
+            }).collect();
+            outcome.change_issues(&changes);
+        }
+    }
+
+    /// whether every issue the check could find is turned off, so its side effects can be skipped too
+    pub fn is_off(&self, id: &str) -> bool {
+        let Some((_, _, kinds)) = CHECKS.iter().find(|(i, ..)| *i == id) else { return false };
+        kinds.iter().all(|kind| ["warnings", "failures"].iter().all(|level| {
+            [kind, level, "severity"].into_iter().find_map(|k| self.severities.iter().rev().find(|(c, key, ..)| *c == id && *key == k))
+                .is_some_and(|(.., severity, _)| *severity == Severity::Off)
+        }))
+    }
+
+    /// which config files were used, and where each setting that isn't a default came from
+    pub fn outcome(&self) -> Outcome {
+        let message = if self.files.is_empty() {
+            "no config file found, using the defaults".into()
+        } else {
+            format!("using {}",self.files.iter().map(|f| format!("'{}'",f.display())).collect::<Vec<String>>().join(", "))
+        };
+        let defaults: HashMap<&str,f32> = items().iter().map(|(name, default, _)| (*name, *default)).collect();
+        let mut details: Vec<String> = self.sources.iter()
+            .filter(|(name, _)| defaults.get(name.as_str()) != self.items.get(*name))
+            .map(|(name, file)| {
+                let (base, qualifiers) = name.split_at(name.find('[').unwrap_or(name.len()));
+                format!("{}{} = {} from {}",base.to_lowercase(),qualifiers,self.items[name],file)
+            })
+            .collect();
+        for (i, (check, key, severity, source)) in self.severities.iter().enumerate() {
+            if !self.severities[i + 1..].iter().any(|(c, k, ..)| c == check && k == key) {
+                details.push(format!("{} {} = {} from {}",check,key,severity,source));
             }
         }
+        details.sort();
+        let outcome = Outcome::new_full("Config", Status::Pass, message);
+        if details.is_empty() { outcome } else { outcome.with_details(details.join("\n")) }
+    }
+}
+
+/// where config files go for the current user
+fn user_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        PathBuf::from(env::var_os("APPDATA")?)
     } else {
-        eprintln!("{}","Warning: no config file found, creating default config");
-        create_config(default_config.to_string()).unwrap_or(());
+        env::var_os("XDG_CONFIG_HOME").filter(|x| !x.is_empty()).map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?
+    };
+    Some(base.join("validate_toolpath"))
+}
+
+/// adds a config file as the highest priority layer, unless it's already there
+fn push_layer(files: &mut Vec<PathBuf>, file: PathBuf) {
+    //the same file can turn up twice, e.g. when the toolpath is next to the executable
+    let canonical = |f: &PathBuf| fs::canonicalize(f).unwrap_or(f.clone());
+    let this = canonical(&file);
+    files.retain(|f| canonical(f) != this);
+    files.push(file);
+}
+
+/// config files in the order they're layered, each overriding the ones before: next to the executable,
+/// the user config directory, next to the toolpath, then the `--config` file
+fn layers(args: &Args, toolpath_dir: Option<&Path>) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    dirs.extend(env::current_exe().ok().and_then(|e| e.parent().map(Path::to_path_buf)));
+    dirs.extend(user_dir());
+    dirs.extend(toolpath_dir.map(|d| if d.as_os_str().is_empty() { PathBuf::from(".") } else { d.to_path_buf() }));
+    let mut files: Vec<PathBuf> = Vec::new();
+    for dir in dirs {
+        if let Some(file) = ["config.toml", "config.txt"].iter().map(|f| dir.join(f)).find(|f| f.is_file()) {
+            push_layer(&mut files, file);
+        }
+    }
+    if let Some(file) = &args.config {
+        push_layer(&mut files, file.clone());
+    }
+    files
+}
+
+/// reads and merges every config file for toolpaths in `toolpath_dir` on top of the defaults.
+/// a default config.toml is written to the user config directory if there are none
+pub fn read_config(args: &Args, toolpath_dir: Option<&Path>) -> Result<Config,String> {
+    let items = items();
+    let mut config = Config {
+        items: items.iter().map(|(name, default, _)| (name.to_string(), *default)).collect(),
+        sources: HashMap::new(),
+        files: layers(args, toolpath_dir),
+        severities: Vec::new(),
+        profiles: Vec::new(),
+        machine_values: Vec::new(),
+    };
+    if let Some(file) = args.config.as_ref().filter(|f| !f.is_file()) {
+        return Err(format!("couldn't find config file '{}'",file.display()));
+    }
+    if config.files.is_empty() {
+        let file = user_dir().unwrap_or_default().join("config.toml");
+        eprintln!("Warning: no config file found, creating default config '{}'",file.display());
+        create_config(&file, default_toml(&items)).unwrap_or(());
+    }
+
+    let mut errors: Vec<String> = Vec::new();
+    for file in config.files.clone() {
+        let contents = fs::read_to_string(&file).map_err(|_| format!("couldn't read config file '{}'",file.display()))?;
+        if file.extension().is_some_and(|e| e == "txt") {
+            eprintln!("Warning: '{}' is in the deprecated config.txt format, move its settings into sections of a config.toml",file.display());
+            read_legacy(&contents, &mut config, &file).unwrap_or_else(|e| errors.push(e));
+        } else {
+            read_toml(&contents, &mut config, &file).unwrap_or_else(|e| {
+                errors.extend(e.iter().map(|e| format!("{} {}",file.display(),e)));
+            });
+        }
+    }
+    if let Some(machine) = &args.machine {
+        config.use_machine(machine).unwrap_or_else(|e| errors.push(e));
+    }
+    if errors.is_empty() { Ok(config) } else { Err(errors.join("\n")) }
+}
+
+/// the config file `import` writes to: the `--config` file, or the one in the user config directory
+pub fn import_file(args: &Args) -> Option<PathBuf> {
+    args.config.clone().or_else(|| user_dir().map(|d| d.join("config.toml")))
+}
+
+/// sets items in one section of a config.toml, leaving the rest of the file as it was.
+/// the file is created with the defaults if it doesn't exist, and the section is added to the end if it's missing
+pub fn update(path: &Path, section: &str, values: &[(&str,f32)]) -> Result<(),String> {
+    if path.extension().is_some_and(|e| e == "txt") {
+        return Err(format!("'{}' is in the deprecated config.txt format, use a config.toml",path.display()));
+    }
+    if !path.exists() {
+        create_config(path, default_toml(&items())).map_err(|_| format!("couldn't create config file '{}'",path.display()))?;
+    }
+    let contents = fs::read_to_string(path).map_err(|_| format!("couldn't read config file '{}'",path.display()))?;
+    let mut lines: Vec<String> = contents.lines().map(String::from).collect();
+    let header = |line: &str| strip_comment(line).trim().strip_prefix('[').and_then(|h| h.strip_suffix(']')).map(split_key);
+    let start = match lines.iter().position(|l| header(l) == Some(split_key(section))) {
+        Some(start) => start + 1,
+        None => {
+            lines.push(String::new());
+            lines.push(format!("[{}]",section));
+            lines.len()
+        }
+    };
+    let mut end = lines[start..].iter().position(|l| header(l).is_some()).map_or(lines.len(), |i| start + i);
+    for (name, value) in values {
+        let line = format!("{} = {:?}",name.to_lowercase(),value);
+        let existing = lines[start..end].iter().position(|l| {
+            strip_comment(l).split_once('=').is_some_and(|(key, _)| unquote(key).eq_ignore_ascii_case(name))
+        });
+        match existing {
+            Some(i) => lines[start + i] = line,
+            None => {
+                //after the last item, before any blank lines and comments for the next section
+                let last = lines[start..end].iter().rposition(|l| !strip_comment(l).trim().is_empty()).map_or(start, |i| start + i + 1);
+                lines.insert(last, line);
+                end += 1;
+            }
+        }
+    }
+    fs::write(path, lines.join("\n") + "\n").map_err(|_| format!("couldn't write config file '{}'",path.display()))
+}
+
+/// the old flat format, with a `NAME = value` on each line
+fn read_legacy(contents: &str, config: &mut Config, file: &Path) -> Result<(),String> {
+    for (i, l) in contents.lines().enumerate() {
+        if !(l.starts_with("//") || l.starts_with("#")) {
+            if let Some(k) = l.split_terminator(&[' ', '=', ':'][..]).next() {
+                let k = k.trim();
+                let rest = &l[l.find(k).unwrap_or(0) + k.len()..];
+                let k = current_name(k.to_string(), &format!("{} line {}",file.display(),i + 1));
+                let k = k.as_str();
+                //qualified items like MAX_CUT_FEED[T2] are allowed for any item that exists
+                let known = config.items.contains_key(k) ||
+                    k.split_once('[').is_some_and(|(base,_)| config.items.contains_key(base));
+                if known {
+                    if let Some(new) = NUM_RE.find(rest) {
+                        let value = new.as_str().parse::<f32>()
+                            .map_err(|_| format!("{} line {}: '{}' isn't a number",file.display(),i + 1,new.as_str()))?;
+                        config.set(k.into(), value, file);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn section_of(name: &str) -> &'static str {
+    SECTIONS.iter().find(|(_, names)| names.contains(&name)).map_or("checks", |(section, _)| section)
+}
+
+/// where the items in a config.toml section go
+enum Target {
+    /// `[machine]` and `[checks.*]`, holding just the items listed for them, and severities if it's for a check
+    Section(&'static [&'static str],Option<&'static str>),
+    /// `[machines.NAME]`
+    Machine(String),
+    /// `[materials.NAME]`, `[tools.T2]`, or both like `[materials.aluminum.tools.T2]`, for items with qualifiers
+    Qualified(String),
+}
+
+/// the text inside a "basic" or 'literal' toml string
+fn string(text: &str) -> Option<&str> {
+    let text = text.trim();
+    ['"', '\''].into_iter().find_map(|q| text.strip_prefix(q).and_then(|t| t.strip_suffix(q)))
+}
+
+fn unquote(part: &str) -> String {
+    string(part).unwrap_or(part.trim()).to_string()
+}
+
+/// splits a dotted key like `tools."0.25"` into its parts, keeping dots inside quotes
+fn split_key(key: &str) -> Vec<String> {
+    let mut parts: Vec<String> = vec![String::new()];
+    let mut quote: Option<char> = None;
+    for c in key.chars() {
+        match c {
+            '"' | '\'' if quote.is_none() => quote = Some(c),
+            _ if quote == Some(c) => quote = None,
+            '.' if quote.is_none() => { parts.push(String::new()); continue; }
+            _ => {}
+        }
+        parts.last_mut().unwrap().push(c);
     }
-    config_items
+    parts.iter().map(|p| unquote(p)).collect()
 }
 
-fn create_config(default: String) -> std::io::Result<()> {
-    let mut file = File::create("config.txt")?;
+fn parse_section(header: &str) -> Result<Target,String> {
+    let parts = split_key(header);
+    let parts: Vec<&str> = parts.iter().map(|p| p.as_str()).collect();
+    let names = SECTIONS.iter().find(|(section, _)| *section == header.trim()).map(|(_, names)| *names);
+    let check = match parts.as_slice() {
+        ["checks", id] => CHECKS.iter().find(|(c, ..)| c == id).map(|(c, ..)| *c),
+        _ => None,
+    };
+    if names.is_some() || check.is_some() {
+        return Ok(Target::Section(names.unwrap_or(&[]), check));
+    }
+    match parts.as_slice() {
+        ["machines", name] => Ok(Target::Machine(name.to_string())),
+        ["materials", _] | ["tools", _] | ["materials", _, "tools", _] | ["tools", _, "materials", _] => {
+            let mut qualifiers: Vec<String> = Vec::new();
+            for pair in parts.chunks(2) {
+                let valid = pair[0] == "materials" || pair[1].strip_prefix(['T','t']).is_some_and(|n| n.parse::<u32>().is_ok())
+                    || pair[1].parse::<f32>().is_ok();
+                if !valid {
+                    return Err(format!("'{}' isn't a tool number like T2 or a diameter like \"0.25\"",pair[1]));
+                }
+                qualifiers.push(pair[1].to_string());
+            }
+            Ok(Target::Qualified(qualifiers.join(",")))
+        }
+        _ => Err(format!("unknown section [{}]",header.trim())),
+    }
+}
+
+/// everything after a # that isn't in a string
+fn strip_comment(line: &str) -> &str {
+    let mut quote: Option<char> = None;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' | '\'' if quote.is_none() => quote = Some(c),
+            _ if quote == Some(c) => quote = None,
+            '#' if quote.is_none() => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn parse_value(name: &str, text: &str) -> Result<f32,String> {
+    let text = text.trim();
+    if let Some(word) = string(text) {
+        let named: Vec<&(&str,&str,f32)> = NAMED_VALUES.iter().filter(|(n,_,_)| *n == name).collect();
+        return named.iter().find(|(_,w,_)| w.eq_ignore_ascii_case(word)).map(|(_,_,v)| *v).ok_or_else(|| {
+            if named.is_empty() {
+                format!("{} needs a number, not \"{}\"",name.to_lowercase(),word)
+            } else {
+                format!("{} needs one of {}",name.to_lowercase(),named.iter().map(|(_,w,_)| format!("\"{}\"",w)).collect::<Vec<String>>().join(", "))
+            }
+        });
+    }
+    let value = text.replace('_', "").parse::<f32>()
+        .map_err(|_| format!("'{}' isn't a number",text))?;
+    if WHOLE_NUMBERS.contains(&name) && value.fract() != 0.0 {
+        return Err(format!("{} needs a whole number, not {}",name.to_lowercase(),value));
+    }
+    Ok(value)
+}
+
+fn read_toml(contents: &str, config: &mut Config, file: &Path) -> Result<(),Vec<String>> {
+    let mut errors: Vec<String> = Vec::new();
+    let mut target: Option<Target> = None;
+    let mut bad_section = false;
+    for (i, line) in contents.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            target = None;
+            bad_section = false;
+            match header.strip_suffix(']').ok_or("section header is missing a ]".to_string()).and_then(parse_section) {
+                Ok(t) => {
+                    if let Target::Machine(name) = &t {
+                        config.profiles.push(name.clone());
+                    }
+                    target = Some(t);
+                }
+                Err(error) => {
+                    errors.push(format!("line {}: {}",i + 1,error));
+                    bad_section = true;
+                }
+            }
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            errors.push(format!("line {}: expected a key = value",i + 1));
+            continue;
+        };
+        let name = current_name(unquote(key).to_uppercase(), &format!("{} line {}",file.display(),i + 1));
+        //keys in a bad section are skipped rather than each reported again
+        if bad_section {
+            continue;
+        }
+        let Some(target) = &target else {
+            if SECTIONS.iter().all(|(_, names)| !names.contains(&name.as_str())) {
+                errors.push(format!("line {}: unknown key '{}'",i + 1,key.trim()));
+            } else {
+                errors.push(format!("line {}: {} belongs in [{}]",i + 1,key.trim(),section_of(&name)));
+            }
+            continue;
+        };
+        let kinds = match target {
+            Target::Section(_, Some(check)) => CHECKS.iter().find(|(id, ..)| id == check).map_or(&[][..], |(_, _, kinds)| *kinds),
+            _ => &[],
+        };
+        if let (Target::Section(_, Some(check)), Some(key)) = (target, SEVERITY_KEYS.iter().chain(kinds).find(|k| k.eq_ignore_ascii_case(&name))) {
+            match parse_severity(key, value) {
+                Ok(severity) => config.severities.push((check, key, severity, format!("'{}'",file.display()))),
+                Err(error) => errors.push(format!("line {}: {}",i + 1,error)),
+            }
+            continue;
+        }
+        let machine_items = SECTIONS[0].1;
+        let allowed = match target {
+            Target::Section(names, _) => names.contains(&name.as_str()),
+            Target::Machine(_) => machine_items.contains(&name.as_str()),
+            Target::Qualified(_) => config.items.contains_key(&name) && !machine_items.contains(&name.as_str()),
+        };
+        if !allowed {
+            errors.push(format!("line {}: unknown key '{}' in this section{}",i + 1,key.trim(),
+                if config.items.contains_key(&name) { format!(", it belongs in [{}]",section_of(&name)) } else { String::new() }
+            ));
+            continue;
+        }
+        let value = match parse_value(&name, value) {
+            Ok(v) => v,
+            Err(error) => { errors.push(format!("line {}: {}",i + 1,error)); continue; }
+        };
+        match target {
+            Target::Section(..) => config.set(name, value, file),
+            Target::Machine(profile) => if let Some(item) = machine_items.iter().find(|m| **m == name) {
+                config.machine_values.push((profile.clone(), item, value, file.into()));
+            },
+            Target::Qualified(qualifiers) => config.set(format!("{}[{}]",name,qualifiers), value, file),
+        }
+    }
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
+
+fn comment(description: &str) -> String {
+    description.lines().map(|l| format!("# {}\n",l)).collect()
+}
+
+fn default_toml(items: &[Item]) -> String {
+    let mut toml = String::from("# settings for validate_toolpath. every item is optional, and the default is used for anything left out\n");
+    toml += "#\n# every [checks.*] section can also set how much what the check finds counts, with severity = \"off\", \"warning\"\n";
+    toml += "# or \"fail\". warnings = and failures = set it for just the problems the check would otherwise warn or fail for,\n";
+    toml += "# and each kind of issue listed under the section can be set on its own, like too_many = \"fail\" in [checks.passes]\n";
+    let kinds = |section: &str| CHECKS.iter().find(|(id, ..)| format!("checks.{}",id) == section)
+        .map_or(String::new(), |(_, _, kinds)| format!("# kinds of issue: {}\n",kinds.join(", ")));
+    for (section, names) in SECTIONS {
+        toml += &format!("\n[{}]\n{}",section,kinds(section));
+        for (name, default, description) in items.iter().filter(|(n,_,_)| names.contains(n)) {
+            toml += &format!("\n{}# default: {:?}\n{} = {:?}\n",comment(description),default,name.to_lowercase(),default);
+        }
+    }
+    for (id, ..) in CHECKS.iter().filter(|(id, ..)| SECTIONS.iter().all(|(section, _)| *section != format!("checks.{}",id))) {
+        toml += &format!("\n[checks.{}]\n{}",id,kinds(&format!("checks.{}",id)));
+    }
+    //qualified defaults, grouped by what they're for
+    let mut qualified: Vec<(&str,Vec<String>)> = Vec::new();
+    for (name, default, _) in items {
+        if let Some((base, qualifiers)) = name.split_once('[') {
+            let line = format!("{} = {:?}\n",base.to_lowercase(),default);
+            let qualifiers = qualifiers.trim_end_matches(']');
+            match qualified.iter_mut().find(|(q,_)| *q == qualifiers) {
+                Some((_, lines)) => lines.push(line),
+                None => qualified.push((qualifiers, vec![line])),
+            }
+        }
+    }
+    toml += "\n# items in [materials.NAME], [tools.T2] and [tools.\"0.25\"] sections are for cuts in that material, or with that\n";
+    toml += "# tool number or diameter. when several match a cut, a tool number beats a diameter, which beats a material,\n";
+    toml += "# and a section naming another as well, like [materials.aluminum.tools.T2], beats one naming just the tool\n";
+    for (material, lines) in qualified {
+        toml += &format!("\n[materials.{}]\n{}",material,lines.concat());
+    }
+    toml += "\n# a machine profile overrides [machine] when it's picked with --machine, e.g. --machine big-router\n";
+    toml += "# [machines.big-router]\n# soft_max_x = 48.0\n# soft_max_y = 96.0\n";
+    toml
+}
+
+fn create_config(path: &Path, default: String) -> std::io::Result<()> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = File::create(path)?;
     file.write_all(default.as_bytes())?;
     Ok(())
 }
+
+/// what a qualified config item like `MAX_CUT_FEED[aluminum,T2]` can be matched against
+#[derive(Clone,Debug,Default)]
+pub struct Qualifiers<'a> {
+    pub material: Option<&'a str>,
+    pub tool_number: Option<u32>,
+    pub diameter: Option<f32>,
+}
+
+/// gets a config item, preferring the qualified version that matches best. a tool number beats a diameter,
+/// which beats a material, and naming one of those as well beats naming it alone
+pub fn lookup(config_items: &HashMap<String,f32>, name: &str, q: &Qualifiers) -> f32 {
+    let mut best: Option<((bool,bool,bool),&String,f32)> = None;
+    for (k,v) in config_items {
+        let rank = if k == name {
+            (false,false,false)
+        } else if let Some(list) = k.strip_prefix(name).and_then(|r| r.strip_prefix('[')).and_then(|r| r.strip_suffix(']')) {
+            if !list.split(',').all(|item| qualifier_matches(item.trim(), q)) {
+                continue;
+            }
+            let has = |kind: Qualifier| list.split(',').any(|item| qualifier_kind(item.trim()) == kind);
+            (has(Qualifier::ToolNumber), has(Qualifier::Diameter), has(Qualifier::Material))
+        } else {
+            continue;
+        };
+        //the same qualifiers written two ways, like T2 and t2, can only be told apart by how they're written
+        if best.is_none_or(|(r,bk,_)| rank > r || (rank == r && k < bk)) {
+            best = Some((rank,k,*v));
+        }
+    }
+    best.map(|(_,_,v)| v).unwrap_or_else(|| panic!("missing config item {}", name))
+}
+
+#[derive(PartialEq)]
+enum Qualifier {
+    ToolNumber,
+    Diameter,
+    Material,
+}
+
+fn qualifier_kind(item: &str) -> Qualifier {
+    if item.strip_prefix(['T','t']).is_some_and(|n| n.parse::<u32>().is_ok()) {
+        Qualifier::ToolNumber
+    } else if item.parse::<f32>().is_ok() {
+        Qualifier::Diameter
+    } else {
+        Qualifier::Material
+    }
+}
+
+fn qualifier_matches(item: &str, q: &Qualifiers) -> bool {
+    match qualifier_kind(item) {
+        Qualifier::ToolNumber => q.tool_number.is_some_and(|n| item[1..].parse() == Ok(n)),
+        Qualifier::Diameter => q.diameter.is_some_and(|diameter| item.parse::<f32>().is_ok_and(|d| (diameter - d).abs() < 0.001)),
+        Qualifier::Material => q.material.is_some_and(|m| m.eq_ignore_ascii_case(item)),
+    }
+}
+
+/// every material that has its own config items
+pub fn materials(config_items: &HashMap<String,f32>) -> Vec<String> {
+    let mut materials: Vec<String> = config_items.keys()
+        .filter_map(|k| k.split_once('[').and_then(|(_,r)| r.strip_suffix(']')))
+        .flat_map(|list| list.split(','))
+        .map(|item| item.trim().to_lowercase())
+        .filter(|item| qualifier_kind(item) == Qualifier::Material)
+        .collect();
+    materials.sort();
+    materials.dedup();
+    materials
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Issue;
+
+    fn config(severities: &[(&'static str,Severity)]) -> Config {
+        Config {
+            items: HashMap::new(),
+            sources: HashMap::new(),
+            files: Vec::new(),
+            severities: severities.iter().map(|(key, severity)| ("spindle", *key, *severity, "'config.toml'".into())).collect(),
+            profiles: Vec::new(),
+            machine_values: Vec::new(),
+        }
+    }
+
+    /// the defaults with a config.toml read over them
+    fn read(contents: &str) -> Result<Config,Vec<String>> {
+        let mut config = config(&[]);
+        config.severities.clear();
+        config.items = items().iter().map(|(name, default, _)| (name.to_string(), *default)).collect();
+        read_toml(contents, &mut config, Path::new("config.toml")).map(|_| config)
+    }
+
+    fn spindle() -> Vec<Outcome> {
+        vec![Outcome::from_issues("Spindle", vec![
+            Issue::new(Status::Warning, "out_of_range", "spindle speed of 1000 at line 12 is outside the machine range".into()),
+            Issue::new(Status::Fail, "stopped", "cutting with the spindle stopped, first at line 32".into()),
+        ], String::new())]
+    }
+
+    #[test]
+    fn turning_off_failures_keeps_warnings() {
+        let mut outcomes = spindle();
+        config(&[("failures", Severity::Off)]).apply_severities(&mut outcomes);
+        assert_eq!(outcomes[0].status, Status::Warning);
+        assert_eq!(outcomes[0].issue_statuses(), vec![Status::Warning, Status::Suppressed]);
+    }
+
+    #[test]
+    fn warnings_count_as_failures_beside_a_failure() {
+        let mut outcomes = spindle();
+        config(&[("warnings", Severity::Fail)]).apply_severities(&mut outcomes);
+        assert_eq!(outcomes[0].status, Status::Fail);
+        assert_eq!(outcomes[0].issue_statuses(), vec![Status::Fail, Status::Fail]);
+        assert!(outcomes[0].message.lines().next().unwrap().ends_with("(counted as FAIL by warnings = \"fail\" from 'config.toml')"));
+    }
+
+    #[test]
+    fn a_check_is_off_only_when_nothing_turns_its_issues_back_on() {
+        assert!(config(&[("severity", Severity::Off)]).is_off("spindle"));
+        assert!(!config(&[("severity", Severity::Off), ("stopped", Severity::Fail)]).is_off("spindle"));
+        assert!(!config(&[("failures", Severity::Off)]).is_off("spindle"));
+        assert!(config(&[("warnings", Severity::Off), ("failures", Severity::Off)]).is_off("spindle"));
+    }
+
+    #[test]
+    fn reads_basic_and_literal_strings_and_inline_comments() {
+        let config = read("[checks.spindle]\nseverity = 'warning' # only a warning\n\n[checks.cut_direction]\ncut_direction = \"climb\" # 'climb' only\n\n[checks.passes]\nmax_passes = 8 # not 6\n").unwrap();
+        assert_eq!(config.severities.iter().map(|(c, k, s, _)| (*c, *k, *s)).collect::<Vec<_>>(), vec![("spindle", "severity", Severity::Warning)]);
+        assert_eq!(config.items["CUT_DIRECTION"], 1.0);
+        assert_eq!(config.items["MAX_PASSES"], 8.0);
+        assert_eq!(config.sources["MAX_PASSES"], "'config.toml'");
+    }
+
+    #[test]
+    fn reads_qualified_tables() {
+        let config = read("[tools.\"0.25\"]\nmax_cut_feed = 90\n\n[materials.'aluminum'.tools.T2]\nmax_cut_feed = 40\n\n[machines.shop]\nsoft_max_x = 48\n").unwrap();
+        assert_eq!(config.items["MAX_CUT_FEED[0.25]"], 90.0);
+        assert_eq!(config.items["MAX_CUT_FEED[aluminum,T2]"], 40.0);
+        assert_eq!(config.profiles, vec!["shop"]);
+        assert_eq!(config.items["SOFT_MAX_X"], 24.0);
+    }
+
+    #[test]
+    fn reports_every_bad_line() {
+        let errors = read("max_passes = 8\n[checks.passes\n[checks.nothing]\nfoo = 1\n[checks.depth]\nmax_passes = 8\ndepth_threshold = 'deep'\nseverity = \"loud\"\njust words\nmaterial_thickness = '#'\n").err().unwrap();
+        assert_eq!(errors, vec![
+            "line 1: max_passes belongs in [checks.passes]",
+            "line 2: section header is missing a ]",
+            "line 3: unknown section [checks.nothing]",
+            "line 6: unknown key 'max_passes' in this section, it belongs in [checks.passes]",
+            "line 7: depth_threshold needs a number, not \"deep\"",
+            "line 8: severity needs one of \"off\", \"warning\", \"fail\"",
+            "line 9: expected a key = value",
+            "line 10: material_thickness needs a number, not \"#\"",
+        ]);
+    }
+
+    #[test]
+    fn a_kind_of_issue_has_its_own_severity() {
+        let mut outcomes = vec![Outcome::from_issues("Number of Passes", vec![
+            Issue::new(Status::Warning, "too_many", "7 passes detected for T1, too many?".into()),
+            Issue::new(Status::Warning, "stepdown", "stepdown of 0.300 down to z 0.2 at line 40 is more than the limit".into()),
+        ], String::new())];
+        let config = read("[checks.passes]\nwarnings = \"fail\"\ntoo_many = \"off\"\n").unwrap();
+        config.apply_severities(&mut outcomes);
+        assert_eq!(outcomes[0].status, Status::Fail);
+        assert_eq!(outcomes[0].issue_statuses(), vec![Status::Suppressed, Status::Fail]);
+        assert!(outcomes[0].message.starts_with("7 passes detected for T1, too many? (turned off by too_many = \"off\" from 'config.toml')"));
+    }
+
+    #[test]
+    fn tool_numbers_beat_diameters_beat_materials() {
+        let mut items: HashMap<String,f32> = [
+            ("MAX_CUT_FEED", 150.0), ("MAX_CUT_FEED[aluminum]", 60.0), ("MAX_CUT_FEED[0.25]", 90.0),
+            ("MAX_CUT_FEED[T2]", 40.0), ("MAX_CUT_FEED[aluminum,0.25]", 50.0), ("MAX_CUT_FEED[aluminum,T2]", 30.0),
+        ].into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+        let feed = |items: &HashMap<String,f32>, material, tool_number, diameter| {
+            lookup(items, "MAX_CUT_FEED", &Qualifiers { material, tool_number, diameter })
+        };
+        assert_eq!(feed(&items, None, None, None), 150.0);
+        assert_eq!(feed(&items, Some("Aluminum"), None, None), 60.0);
+        assert_eq!(feed(&items, Some("plywood"), None, Some(0.25)), 90.0);
+        assert_eq!(feed(&items, Some("aluminum"), None, Some(0.25)), 50.0);
+        assert_eq!(feed(&items, Some("aluminum"), Some(3), Some(0.25)), 50.0);
+        assert_eq!(feed(&items, Some("aluminum"), Some(2), Some(0.25)), 30.0);
+        //the tool number alone still wins over a material and diameter together
+        items.remove("MAX_CUT_FEED[aluminum,T2]");
+        assert_eq!(feed(&items, Some("aluminum"), Some(2), Some(0.25)), 40.0);
+    }
+
+    #[test]
+    fn the_config_option_overrides_the_file_next_to_the_toolpath() {
+        let dir = env::temp_dir().join(format!("validate_toolpath_layers_{}",std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (beside, chosen) = (dir.join("config.toml"), dir.join("chosen.toml"));
+        fs::write(&beside, "[checks.passes]\nmin_passes = 2\nmax_passes = 8\n").unwrap();
+        fs::write(&chosen, "[checks.passes]\nmax_passes = 9\n").unwrap();
+        let args = Args { config: Some(chosen.clone()), ..Args::default() };
+        let config = read_config(&args, Some(&dir));
+        //the file beside the toolpath is only layered once, even when it's also the --config file
+        let same = read_config(&Args { config: Some(beside.clone()), ..Args::default() }, Some(&dir)).map(|c| c.files);
+        fs::remove_dir_all(&dir).unwrap();
+        let config = config.unwrap();
+        assert_eq!(config.files[config.files.len() - 2..], [beside.clone(), chosen.clone()]);
+        assert_eq!((config.items["MIN_PASSES"], config.items["MAX_PASSES"]), (2.0, 9.0));
+        assert_eq!(config.sources["MAX_PASSES"], format!("'{}'",chosen.display()));
+        assert_eq!(same.unwrap().iter().filter(|f| **f == beside).count(), 1);
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::{HashMap,HashSet};
+use crate::estimate::{self, Machine};
+use crate::gcode::Move;
+use crate::passes;
+use crate::reference::{self, Feature};
+use crate::render::Highlight;
+use crate::{Issue, Outcome, Point, Program, Status};
+
+fn changes(name: &str, changes: Vec<String>, same: &str) -> Outcome {
+    Outcome::from_issues(name, changes.into_iter().map(|c| Issue::new(Status::Warning, "changed", c)).collect(), same.into())
+}
+
+fn value(v: Option<f32>) -> String {
+    v.map_or("?".into(), |v| format!("{:.3}",v))
+}
+
+fn point(p: Point) -> String {
+    format!("({}, {}, {})",value(p.x),value(p.y),value(p.z))
+}
+
+/// every tool in the order it's first used
+fn tools(program: &Program) -> Vec<String> {
+    let mut tools: Vec<String> = Vec::new();
+    for mv in &program.moves {
+        let label = mv.tool_label();
+        if !tools.contains(&label) {
+            tools.push(label);
+        }
+    }
+    tools
+}
+
+fn diff_tools(old: &Program, new: &Program) -> Outcome {
+    let (old, new) = (tools(old), tools(new));
+    let mut changes: Vec<String> = Vec::new();
+    changes.extend(old.iter().filter(|t| !new.contains(t)).map(|t| format!("removed {}",t)));
+    changes.extend(new.iter().filter(|t| !old.contains(t)).map(|t| format!("added {}",t)));
+    if changes.is_empty() && old != new {
+        changes.push(format!("tool order changed from {} to {}",old.join(", "),new.join(", ")));
+    }
+    self::changes("Tools", changes, "same tools")
+}
+
+fn diff_stock(old: &Program, new: &Program) -> Outcome {
+    let mut changes: Vec<String> = Vec::new();
+    if old.material != new.material {
+        changes.push(format!("material changed from {} to {}",
+            old.material.as_deref().unwrap_or("unknown"), new.material.as_deref().unwrap_or("unknown")));
+    }
+    for (i, axis) in ["x", "y", "z"].iter().enumerate() {
+        let (a, b) = (old.material_size[i], new.material_size[i]);
+        if a.zip(b).is_none_or(|(a, b)| (a - b).abs() > 0.001) && (a.is_some() || b.is_some()) {
+            changes.push(format!("stock {} changed from {} to {}",axis,value(a),value(b)));
+        }
+    }
+    self::changes("Stock", changes, "same stock")
+}
+
+fn extents(program: &Program) -> (Point,Point) {
+    let thickness = program.material_size.z;
+    program.moves.iter().filter(|m| m.is_cutting(thickness))
+        .fold((Point::empty(), Point::empty()), |(min, max), m| (min.min(m.to), max.max(m.to)))
+}
+
+fn diff_extents(old: &Program, new: &Program) -> Outcome {
+    let ((old_min, old_max), (new_min, new_max)) = (extents(old), extents(new));
+    let mut changes: Vec<String> = Vec::new();
+    let moved = |a: Point, b: Point| (0..3).any(|i| a[i].zip(b[i]).is_none_or(|(a, b)| (a - b).abs() > 0.001) && (a[i].is_some() || b[i].is_some()));
+    if moved(old_min, new_min) {
+        changes.push(format!("lowest cut corner moved from {} to {}",point(old_min),point(new_min)));
+    }
+    if moved(old_max, new_max) {
+        changes.push(format!("highest cut corner moved from {} to {}",point(old_max),point(new_max)));
+    }
+    self::changes("Extents", changes, "same extents")
+}
+
+/// the depths each operation cuts at, with the tool number it's paired up by if it has one, or else its label
+fn pass_depths(program: &Program, config_items: &HashMap<String,f32>) -> Vec<(Option<u32>,String,Vec<f32>)> {
+    let ops = passes::operations(&program.moves, program.material_size.z,
+        *config_items.get("PASS_FREQUENCY_THRESHOLD").unwrap() as usize,
+    );
+    ops.iter().filter(|op| !op.levels.is_empty())
+        .map(|op| (op.moves[0].setup.tool_number, op.label(), op.levels.iter().map(|l| l.z).collect()))
+        .collect()
+}
+
+/// pairs each tool's operations in the old program with the same tool's in the new one, in the order they're cut
+fn pass_changes(old: &[(Option<u32>,String,Vec<f32>)], new: &[(Option<u32>,String,Vec<f32>)]) -> Vec<String> {
+    let list = |depths: &[f32]| depths.iter().map(|z| format!("{}",z)).collect::<Vec<String>>().join(", ");
+    let same_tool = |a: &(Option<u32>,String,Vec<f32>), b: &(Option<u32>,String,Vec<f32>)| match (a.0, b.0) {
+        (Some(a), Some(b)) => a == b,
+        _ => a.1 == b.1,
+    };
+    let mut changes: Vec<String> = Vec::new();
+    let mut seen: Vec<&(Option<u32>,String,Vec<f32>)> = Vec::new();
+    for op in old {
+        if seen.iter().any(|s| same_tool(s, op)) {
+            continue;
+        }
+        seen.push(op);
+        let before: Vec<&Vec<f32>> = old.iter().filter(|o| same_tool(o, op)).map(|o| &o.2).collect();
+        let after: Vec<&Vec<f32>> = new.iter().filter(|n| same_tool(n, op)).map(|n| &n.2).collect();
+        let tool = &op.1;
+        if after.is_empty() {
+            changes.push(format!("{} is no longer used, it cut passes at z {}",tool,before.iter().map(|d| list(d)).collect::<Vec<String>>().join("; ")));
+            continue;
+        }
+        for i in 0..before.len().max(after.len()) {
+            match (before.get(i), after.get(i)) {
+                (Some(a), Some(b)) if a == b => {}
+                (Some(a), Some(b)) => changes.push(format!("{} passes changed from z {} to z {}",tool,list(a),list(b))),
+                (Some(a), None) => changes.push(format!("{} passes at z {} are gone",tool,list(a))),
+                (None, Some(b)) => changes.push(format!("{} has new passes at z {}",tool,list(b))),
+                (None, None) => {}
+            }
+        }
+    }
+    for op in new.iter().filter(|n| !old.iter().any(|o| same_tool(o, n))) {
+        changes.push(format!("new tool {} cuts passes at z {}",op.1,list(&op.2)));
+    }
+    changes
+}
+
+fn diff_passes(old: &Program, new: &Program, config_items: &HashMap<String,f32>) -> Outcome {
+    let changes = pass_changes(&pass_depths(old, config_items), &pass_depths(new, config_items));
+    self::changes("Pass Depths", changes, "same pass depths")
+}
+
+fn holes(program: &Program, config_items: &HashMap<String,f32>) -> Vec<Feature> {
+    let ops = passes::operations(&program.moves, program.material_size.z,
+        *config_items.get("PASS_FREQUENCY_THRESHOLD").unwrap() as usize,
+    );
+    reference::features(&ops, &program.moves, program.material_size.z).into_iter().filter(|f| f.hole).collect()
+}
+
+fn diff_holes(old: &Program, new: &Program, config_items: &HashMap<String,f32>) -> Outcome {
+    let (old, new) = (holes(old, config_items), holes(new, config_items));
+    let same = |a: &Feature, b: &Feature| {
+        let close = |a: (f32,f32), b: (f32,f32)| (a.0 - b.0).abs() < 0.001 && (a.1 - b.1).abs() < 0.001;
+        close(a.center(), b.center()) && close(a.size(), b.size())
+    };
+    let mut changes: Vec<String> = Vec::new();
+    changes.extend(old.iter().filter(|h| !new.iter().any(|n| same(h, n))).map(|h| format!("removed {}",h.describe())));
+    changes.extend(new.iter().filter(|h| !old.iter().any(|o| same(h, o))).map(|h| format!("added {}, cut at {}",h.describe(),h.place())));
+    self::changes("Holes", changes, &format!("same {} holes",old.len()))
+}
+
+fn diff_time(old: &Program, new: &Program, config_items: &HashMap<String,f32>) -> Outcome {
+    let machine = Machine::from_config(config_items);
+    let (old, new) = (estimate::estimate(&old.moves, &old.state, &machine), estimate::estimate(&new.moves, &new.state, &machine));
+    let delta = |a: f32, b: f32| format!("{}{}",if b < a { "-" } else { "+" },estimate::format_time((b - a).abs()));
+    let mut details: Vec<String> = Vec::new();
+    for (tool, seconds) in &new.per_tool {
+        let before = old.per_tool.iter().find(|(t,_)| t == tool).map_or(0.0, |(_,s)| *s);
+        details.push(format!("{}: {} ({})",tool,estimate::format_time(*seconds),delta(before, *seconds)));
+    }
+    //a second either way is just rounding
+    let out = if (new.total - old.total).abs() < 1.0 {
+        Outcome::new_full("Run Time", Status::Pass, format!("same estimated run time: {}",estimate::format_time(new.total)))
+    } else {
+        Outcome::new_full("Run Time", Status::Warning, format!("estimated run time changed from {} to {} ({})",
+            estimate::format_time(old.total), estimate::format_time(new.total), delta(old.total, new.total)))
+    };
+    out.with_details(details.join("\n"))
+}
+
+/// grid cells that cutting moves pass through
+fn cells(moves: &[Move], thickness: Option<f32>, cell: f32) -> HashSet<(i32,i32)> {
+    let mut cells: HashSet<(i32,i32)> = HashSet::new();
+    for mv in moves.iter().filter(|m| m.is_cutting(thickness)) {
+        let (Some(x), Some(y)) = (mv.from.x, mv.from.y) else { continue };
+        let mut from = (x, y);
+        for to in mv.xy_path() {
+            let steps = ((to.0 - from.0).hypot(to.1 - from.1) / cell * 2.0).ceil().max(1.0) as usize;
+            for i in 0..=steps {
+                let t = i as f32 / steps as f32;
+                let p = (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t);
+                cells.insert(((p.0 / cell).floor() as i32, (p.1 / cell).floor() as i32));
+            }
+            from = to;
+        }
+    }
+    cells
+}
+
+/// areas where one program cuts and the other doesn't cut anywhere nearby
+fn differences(old: &Program, new: &Program, cell: f32) -> Vec<Highlight> {
+    let (a, b) = (cells(&old.moves, old.material_size.z, cell), cells(&new.moves, new.material_size.z, cell));
+    let near = |c: &(i32,i32), other: &HashSet<(i32,i32)>| {
+        (-1..=1).any(|dx| (-1..=1).any(|dy| other.contains(&(c.0 + dx, c.1 + dy))))
+    };
+    let mut different: HashSet<(i32,i32)> = a.iter().filter(|c| !near(c, &b))
+        .chain(b.iter().filter(|c| !near(c, &a)))
+        .copied().collect();
+    let mut regions: Vec<Highlight> = Vec::new();
+    while let Some(&start) = different.iter().next() {
+        different.remove(&start);
+        let mut stack = vec![start];
+        let (mut lo, mut hi) = (start, start);
+        while let Some(c) = stack.pop() {
+            lo = (lo.0.min(c.0), lo.1.min(c.1));
+            hi = (hi.0.max(c.0), hi.1.max(c.1));
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    if different.remove(&(c.0 + dx, c.1 + dy)) {
+                        stack.push((c.0 + dx, c.1 + dy));
+                    }
+                }
+            }
+        }
+        regions.push((
+            (lo.0 as f32 * cell, lo.1 as f32 * cell),
+            ((hi.0 + 1) as f32 * cell, (hi.1 + 1) as f32 * cell),
+        ));
+    }
+    regions.sort_by(|a, b| a.0.0.total_cmp(&b.0.0).then(a.0.1.total_cmp(&b.0.1)));
+    regions
+}
+
+/// compares two revisions of a program. also returns the areas where the cutting paths differ, for plotting
+pub fn diff(old: &Program, new: &Program, config_items: &HashMap<String,f32>) -> (Vec<Outcome>,Vec<Highlight>) {
+    let regions = differences(old, new, *config_items.get("DIFF_TOLERANCE").unwrap());
+    let geometry = changes("Geometry",
+        regions.iter().map(|(lo, hi)| format!("cutting paths differ between ({:.2}, {:.2}) and ({:.2}, {:.2})",lo.0,lo.1,hi.0,hi.1)).collect(),
+        "cutting paths are in the same places",
+    );
+    (vec![
+        diff_tools(old, new),
+        diff_stock(old, new),
+        diff_extents(old, new),
+        diff_passes(old, new, config_items),
+        diff_holes(old, new, config_items),
+        diff_time(old, new, config_items),
+        geometry,
+    ], regions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op(tool_number: Option<u32>, label: &str, depths: &[f32]) -> (Option<u32>,String,Vec<f32>) {
+        (tool_number, label.into(), depths.to_vec())
+    }
+
+    #[test]
+    fn passes_are_paired_by_tool_not_by_order() {
+        //the new program cuts with T2 before T1, and T1's passes are the same
+        let old = [op(Some(1), "T1 (0.25\" end mill)", &[0.5, 0.25]), op(Some(2), "T2 (0.125\" end mill)", &[0.5])];
+        let new = [op(Some(2), "T2 (0.125\" end mill)", &[0.4]), op(Some(1), "T1 (0.25\" end mill)", &[0.5, 0.25])];
+        assert_eq!(pass_changes(&old, &new), vec!["T2 (0.125\" end mill) passes changed from z 0.5 to z 0.4"]);
+    }
+
+    #[test]
+    fn removed_and_new_tools_are_reported_apart_from_changed_passes() {
+        let old = [op(Some(1), "T1 (0.25\" end mill)", &[0.5]), op(None, "0.201\" drill", &[0.0])];
+        let new = [op(Some(1), "T1 (0.25\" end mill)", &[0.5]), op(Some(1), "T1 (0.25\" end mill)", &[0.3]), op(Some(3), "T3 (0.5\" end mill)", &[0.2])];
+        assert_eq!(pass_changes(&old, &new), vec![
+            "T1 (0.25\" end mill) has new passes at z 0.3",
+            "0.201\" drill is no longer used, it cut passes at z 0",
+            "new tool T3 (0.5\" end mill) cuts passes at z 0.2",
+        ]);
+    }
+}
@@ -0,0 +1,182 @@
+use regex::Regex;
+use crate::config::Config;
+use crate::{Issue, Outcome, Status, CHECKS};
+
+lazy_static! {
+    static ref DIRECTIVE_RE: Regex = Regex::new(r"(?i)[(;]\s*validate\s*:\s*([^)]*)").unwrap();
+}
+
+/// a check that's allowed to fail in this program, from `(validate: allow passes)`
+struct Allow {
+    /// the outcome name
+    check: &'static str,
+    directive: String,
+    line: usize,
+    /// the lines it covers, or the whole file
+    lines: Option<(usize,usize)>,
+}
+
+/// everything set with `(validate: ...)` comments in a program
+#[derive(Default)]
+pub struct Directives {
+    /// config items set for just this file, with the line they're on
+    settings: Vec<(usize,String,String)>,
+    allows: Vec<Allow>,
+    errors: Vec<(Status,String)>,
+}
+
+fn check_name(id: &str) -> Option<&'static str> {
+    let id = id.to_lowercase().replace('-', "_");
+    CHECKS.iter().find(|(i, name, _)| *i == id || name.to_lowercase().replace(' ', "_") == id).map(|(_, name, _)| *name)
+}
+
+impl Directives {
+    /// finds the directives on a line of a program:
+    /// `(validate: allow passes)` suppresses a check for the whole file,
+    /// `(validate: begin allow feed_rate)` up to `(validate: end allow feed_rate)` suppresses it for those lines,
+    /// and `(validate: DEPTH_THRESHOLD=0.1)` changes a config item for the whole file
+    pub fn read_line(&mut self, line_number: usize, line: &str) {
+        let Some(c) = DIRECTIVE_RE.captures(line) else { return };
+        let directive = format!("(validate: {})",c[1].trim());
+        let lower = c[1].to_lowercase();
+        let words: Vec<&str> = lower.split_whitespace().collect();
+        let (kind, checks) = match words.as_slice() {
+            ["allow", ..] => ("allow", &words[1..]),
+            ["begin", "allow", ..] => ("begin", &words[2..]),
+            ["end", "allow", ..] => ("end", &words[2..]),
+            _ if c[1].contains('=') => {
+                for setting in c[1].split(',') {
+                    match setting.split_once('=') {
+                        Some((name, value)) => self.settings.push((line_number, name.trim().into(), value.trim().into())),
+                        None => self.errors.push((Status::Error, format!("line {}: expected NAME=value in {}",line_number,directive))),
+                    }
+                }
+                return;
+            }
+            _ => {
+                self.errors.push((Status::Error, format!("line {}: unknown directive {}",line_number,directive)));
+                return;
+            }
+        };
+        let checks: Vec<&str> = checks.iter().flat_map(|c| c.split(',')).filter(|c| !c.is_empty()).collect();
+        if checks.is_empty() {
+            self.errors.push((Status::Error, format!("line {}: {} doesn't name a check",line_number,directive)));
+        }
+        for id in checks {
+            let Some(check) = check_name(id) else {
+                self.errors.push((Status::Error, format!("line {}: unknown check '{}', expected one of {}",line_number,id,
+                    CHECKS.iter().map(|(id, ..)| *id).collect::<Vec<&str>>().join(", "))));
+                continue;
+            };
+            match kind {
+                "allow" => self.allows.push(Allow { check, directive: directive.clone(), line: line_number, lines: None }),
+                "begin" => self.allows.push(Allow { check, directive: directive.clone(), line: line_number, lines: Some((line_number, usize::MAX)) }),
+                _ => match self.allows.iter_mut().rev().find(|a| a.check == check && a.lines.is_some_and(|(_, end)| end == usize::MAX)) {
+                    Some(allow) => allow.lines = allow.lines.map(|(start, _)| (start, line_number)),
+                    None => self.errors.push((Status::Error, format!("line {}: {} has no matching begin",line_number,directive))),
+                },
+            }
+        }
+    }
+
+    /// the config with this file's settings applied on top
+    pub fn config(&self, config: &Config) -> (Config,Option<Outcome>) {
+        let mut config = config.clone();
+        let mut errors = self.errors.clone();
+        for (line, name, value) in &self.settings {
+            if let Err(error) = config.set_from(name, value, format!("(validate:) at line {}",line)) {
+                errors.push((Status::Error, format!("line {}: {}",line,error)));
+            }
+        }
+        let outcome = (!errors.is_empty()).then(|| Outcome::from_issues("Directives", errors.into_iter().map(|(s, e)| Issue::new(s, "directive", e)).collect(), String::new()));
+        (config, outcome)
+    }
+
+    /// whether a check is allowed to fail for the whole file
+    pub fn allows_all(&self, check: &str) -> bool {
+        self.allows.iter().any(|a| a.check == check && a.lines.is_none())
+    }
+
+    /// marks outcomes that are allowed to fail as suppressed, keeping what they found.
+    /// a line range only suppresses the issues that are on lines in the range, so ones that aren't about
+    /// particular lines, like a count of every cut with the spindle stopped, need the check allowed for the whole file
+    pub fn suppress(&self, outcomes: &mut [Outcome]) {
+        for outcome in outcomes.iter_mut().filter(|o| matches!(o.status, Status::Warning | Status::Fail | Status::Error | Status::Skipped)) {
+            let allows: Vec<&Allow> = self.allows.iter().filter(|a| a.check == outcome.name).collect();
+            if let Some(allow) = allows.iter().find(|a| a.lines.is_none()) {
+                outcome.overrule(Status::Suppressed, format!("suppressed by {} at line {}, was {}",allow.directive,allow.line,outcome.status));
+                continue;
+            }
+            let changes: Vec<Option<(Status,String)>> = outcome.issue_lines().into_iter().map(|lines| {
+                let (first, last) = lines?;
+                let allow = allows.iter().find(|a| a.lines.is_some_and(|(start, end)| first >= start && last <= end))?;
+                Some((Status::Suppressed, format!("suppressed by {} at line {}",allow.directive,allow.line)))
+            }).collect();
+            outcome.change_issues(&changes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directives(lines: &[(usize,&str)]) -> Directives {
+        let mut directives = Directives::default();
+        for (number, line) in lines {
+            directives.read_line(*number, line);
+        }
+        directives
+    }
+
+    /// an out of range spindle speed at line 12, and cutting with the spindle stopped from line 30 to 50
+    fn spindle() -> Vec<Outcome> {
+        vec![Outcome::from_issues("Spindle", vec![
+            Issue::new(Status::Warning, "out_of_range", "spindle speed of 1000 at line 12 is outside the machine range".into()).at(12),
+            Issue::new(Status::Fail, "stopped", "cutting with the spindle stopped, first at line 30".into()).at(30),
+            Issue::new(Status::Fail, "stopped", "21 cutting moves with the spindle stopped".into()).between((30, 50)),
+        ], String::new())]
+    }
+
+    #[test]
+    fn a_line_range_suppresses_just_the_issues_inside_it() {
+        let mut outcomes = spindle();
+        directives(&[(10, "(validate: begin allow spindle)"), (20, "(validate: end allow spindle)")]).suppress(&mut outcomes);
+        assert_eq!(outcomes[0].status, Status::Fail);
+        assert_eq!(outcomes[0].issue_statuses(), vec![Status::Suppressed, Status::Fail, Status::Fail]);
+        assert!(outcomes[0].message.starts_with("spindle speed of 1000 at line 12 is outside the machine range (suppressed by (validate: begin allow spindle) at line 10)"));
+    }
+
+    #[test]
+    fn a_count_is_only_suppressed_with_every_line_it_counts() {
+        let mut outcomes = spindle();
+        directives(&[(25, "(validate: begin allow spindle)"), (45, "(validate: end allow spindle)")]).suppress(&mut outcomes);
+        assert_eq!(outcomes[0].issue_statuses(), vec![Status::Warning, Status::Suppressed, Status::Fail]);
+
+        let mut outcomes = spindle();
+        directives(&[(5, "(validate: begin allow spindle)"), (60, "(validate: end allow spindle)")]).suppress(&mut outcomes);
+        assert_eq!(outcomes[0].status, Status::Suppressed);
+        assert!(outcomes[0].message.starts_with("suppressed by (validate: begin allow spindle) at line 5, was FAIL\n"));
+    }
+
+    #[test]
+    fn allowing_a_check_suppresses_issues_without_lines() {
+        let mut outcomes = vec![Outcome::from_issues("Program Sequence", vec![
+            Issue::new(Status::Fail, "no_end", "program has no end: no M30 or M2, the file may be incomplete".into()),
+        ], String::new())];
+        directives(&[(1, "(validate: begin allow sequence)"), (99, "(validate: end allow sequence)")]).suppress(&mut outcomes);
+        assert_eq!(outcomes[0].status, Status::Fail);
+        directives(&[(1, "(validate: allow sequence)")]).suppress(&mut outcomes);
+        assert_eq!(outcomes[0].status, Status::Suppressed);
+    }
+
+    #[test]
+    fn reports_bad_directives() {
+        let directives = directives(&[(3, "(validate: allow nothing)"), (4, "; validate: end allow passes"), (5, "(validate: loosen passes)")]);
+        let errors: Vec<&str> = directives.errors.iter().map(|(_, e)| e.as_str()).collect();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].starts_with("line 3: unknown check 'nothing', expected one of safe_height, depth"));
+        assert_eq!(errors[1], "line 4: (validate: end allow passes) has no matching begin");
+        assert_eq!(errors[2], "line 5: unknown directive (validate: loosen passes)");
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use crate::gcode::{Motion, Move, State};
+use crate::{Issue, Outcome, Status};
+
+/// the motion limits of the machine, in inches, minutes for velocities and seconds for accelerations
+pub struct Machine {
+    pub velocity: [f32;3],
+    pub acceleration: [f32;3],
+    pub junction_deviation: f32,
+    pub tool_change_time: f32,
+}
+impl Machine {
+    pub fn from_config(config_items: &HashMap<String,f32>) -> Machine {
+        let get = |k: &str| *config_items.get(k).unwrap();
+        Machine {
+            velocity: [get("VELOCITY_X"), get("VELOCITY_Y"), get("VELOCITY_Z")],
+            acceleration: [get("ACCELERATION_X"), get("ACCELERATION_Y"), get("ACCELERATION_Z")],
+            junction_deviation: get("JUNCTION_DEVIATION"),
+            tool_change_time: get("TOOL_CHANGE_TIME"),
+        }
+    }
+    /// the fastest speed and acceleration possible in a direction without going over any single axis' limits
+    fn limits(&self, dir: [f32;3]) -> (f32,f32) {
+        let mut speed = f32::MAX;
+        let mut accel = f32::MAX;
+        for ((d, velocity), acceleration) in dir.iter().zip(self.velocity).zip(self.acceleration) {
+            if d.abs() > 0.000001 {
+                speed = speed.min(velocity / 60.0 / d.abs());
+                accel = accel.min(acceleration / d.abs());
+            }
+        }
+        (speed, accel)
+    }
+}
+
+/// estimated run time of a program, in seconds
+#[derive(Clone,Debug,Default)]
+pub struct Estimate {
+    pub total: f32,
+    pub per_tool: Vec<(String,f32)>,
+    pub tool_changes: usize,
+    pub dwells: usize,
+}
+
+/// a move ready for planning, with speeds in inches per second. there's one for nearly every move,
+/// so it keeps just what the planning needs
+struct Segment {
+    length: f32,
+    speed: f32,
+    accel: f32,
+    /// fastest it can start at for the corner with the move before it, 0 after a stop
+    corner: f32,
+    /// index into the tool labels
+    tool: usize,
+}
+
+fn direction(mv: &Move) -> ([f32;3],[f32;3]) {
+    let length = mv.length().max(0.000001);
+    let d = |a: Option<f32>, b: Option<f32>| match (a, b) { (Some(a), Some(b)) => b - a, _ => 0.0 };
+    let dz = d(mv.from.z, mv.to.z) / length;
+    if let (Some(sweep), Some((cx, cy)), Some(x0), Some(y0), Some(x1), Some(y1)) =
+        (mv.sweep(), mv.center, mv.from.x, mv.from.y, mv.to.x, mv.to.y) {
+        //arcs start and end tangent to their radius
+        let xy = (1.0 - dz * dz).max(0.0).sqrt();
+        let tangent = |x: f32, y: f32| {
+            let (rx, ry) = (x - cx, y - cy);
+            let r = rx.hypot(ry).max(0.000001);
+            let s = sweep.signum();
+            [-ry / r * s * xy, rx / r * s * xy, dz]
+        };
+        (tangent(x0, y0), tangent(x1, y1))
+    } else {
+        let dir = [d(mv.from.x, mv.to.x) / length, d(mv.from.y, mv.to.y) / length, dz];
+        (dir, dir)
+    }
+}
+
+/// time for one move with a trapezoidal speed profile
+fn move_time(length: f32, entry: f32, exit: f32, speed: f32, accel: f32) -> f32 {
+    let accel_dist = (speed * speed - entry * entry) / (2.0 * accel);
+    let decel_dist = (speed * speed - exit * exit) / (2.0 * accel);
+    if accel_dist + decel_dist <= length {
+        (speed - entry) / accel + (speed - exit) / accel + (length - accel_dist - decel_dist) / speed
+    } else {
+        let peak = ((2.0 * accel * length + entry * entry + exit * exit) / 2.0).sqrt();
+        ((peak - entry) / accel + (peak - exit) / accel).max(0.0)
+    }
+}
+
+pub fn estimate(moves: &[Move], state: &State, machine: &Machine) -> Estimate {
+    let mut pauses: Vec<(usize,f32,bool)> = state.dwells.iter().map(|d| (d.line, d.seconds, false))
+        .chain(state.tool_changes.iter().map(|t| (t.line, machine.tool_change_time, true)))
+        .collect();
+    pauses.sort_by_key(|p| p.0);
+
+    let mut estimate = Estimate::default();
+    let mut per_tool: Vec<(String,f32)> = Vec::new();
+    let mut add = |tool: &str, seconds: f32| {
+        match per_tool.iter_mut().find(|(t,_)| t == tool) {
+            Some((_,s)) => *s += seconds,
+            None => per_tool.push((tool.into(), seconds)),
+        }
+    };
+    let mut tools: Vec<String> = Vec::new();
+
+    let mut segments: Vec<Segment> = Vec::new();
+    //the direction at the end of the last move, its acceleration and speed, for the corner into the next one
+    let mut last: Option<([f32;3],f32,f32)> = None;
+    let mut pause = pauses.iter().peekable();
+    for mv in moves {
+        let mut stop_before = false;
+        while let Some((_,seconds,tool_change)) = pause.next_if(|p| p.0 < mv.line) {
+            stop_before = true;
+            estimate.total += seconds;
+            if *tool_change {
+                estimate.tool_changes += 1;
+            } else {
+                estimate.dwells += 1;
+                add(&mv.tool_label(), *seconds);
+            }
+        }
+        let length = mv.length();
+        if length < 0.000001 {
+            continue;
+        }
+        let (start_dir, end_dir) = direction(mv);
+        let (max_speed, mut accel) = machine.limits(start_dir);
+        let mut speed = match (mv.motion, mv.setup.feed) {
+            (Motion::Rapid, _) | (_, None) => max_speed,
+            (_, Some(feed)) => (feed / 60.0).min(max_speed),
+        };
+        if let (Some((cx, cy)), Some(x0), Some(y0)) = (mv.center, mv.from.x, mv.from.y) {
+            //keep the centripetal acceleration of arcs within the machine's limits
+            accel = accel.min(machine.acceleration[0].min(machine.acceleration[1]));
+            speed = speed.min((accel * (x0 - cx).hypot(y0 - cy)).sqrt());
+        }
+        //fastest speed the move can start at for the corner before it
+        let corner = match last {
+            Some((prev_dir, prev_accel, prev_speed)) if !stop_before => {
+                let cos = -(0..3).map(|k| prev_dir[k] * start_dir[k]).sum::<f32>();
+                let limit = if cos > 0.999999 {
+                    0.0
+                } else if cos < -0.999999 {
+                    f32::MAX
+                } else {
+                    let sin_half = (0.5 * (1.0 - cos)).sqrt();
+                    (accel.min(prev_accel) * machine.junction_deviation * sin_half / (1.0 - sin_half)).sqrt()
+                };
+                limit.min(prev_speed).min(speed)
+            }
+            _ => 0.0,
+        };
+        last = Some((end_dir, accel, speed));
+        let label = mv.tool_label();
+        let tool = tools.iter().position(|t| *t == label).unwrap_or_else(|| {
+            tools.push(label);
+            tools.len() - 1
+        });
+        segments.push(Segment { length, speed, accel, corner, tool });
+    }
+    for (_,seconds,tool_change) in pause {
+        estimate.total += seconds;
+        if *tool_change { estimate.tool_changes += 1 } else { estimate.dwells += 1 }
+    }
+
+    //fastest speed each move can start at: limited by the corner before it and by stopping in time for later moves
+    let n = segments.len();
+    let mut entry: Vec<f32> = segments.iter().map(|s| s.corner).chain([0.0]).collect();
+    for i in (0..n).rev() {
+        entry[i] = entry[i].min((entry[i + 1] * entry[i + 1] + 2.0 * segments[i].accel * segments[i].length).sqrt());
+    }
+    for i in 0..n {
+        entry[i + 1] = entry[i + 1].min((entry[i] * entry[i] + 2.0 * segments[i].accel * segments[i].length).sqrt());
+    }
+
+    for (i, seg) in segments.iter().enumerate() {
+        let time = move_time(seg.length, entry[i], entry[i + 1], seg.speed, seg.accel);
+        estimate.total += time;
+        add(&tools[seg.tool], time);
+    }
+    estimate.per_tool = per_tool;
+    estimate
+}
+
+pub fn format_time(seconds: f32) -> String {
+    let seconds = seconds.round() as u32;
+    if seconds >= 3600 {
+        format!("{}h {}m {}s", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    } else {
+        format!("{}m {}s", seconds / 60, seconds % 60)
+    }
+}
+
+pub fn check_time(estimate: &Estimate, max_job_time: f32) -> Outcome {
+    let name = "Run Time";
+    let mut details: Vec<String> = estimate.per_tool.iter()
+        .map(|(tool,seconds)| format!("{}: {}",tool,format_time(*seconds)))
+        .collect();
+    if estimate.tool_changes > 0 || estimate.dwells > 0 {
+        details.push(format!("{} tool changes, {} dwells",estimate.tool_changes,estimate.dwells));
+    }
+    let message = format!("estimated run time: {}",format_time(estimate.total));
+    let out = if estimate.total > max_job_time * 60.0 {
+        Outcome::from_issues(name, vec![Issue::new(Status::Warning, "too_long",
+            format!("{}, longer than the maximum job time of {} minutes",message,max_job_time)
+        )], String::new())
+    } else {
+        Outcome::new_full(name, Status::Pass, message)
+    };
+    out.with_details(details.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 10 in/s² on every axis, so reaching 60 in/min takes 0.1 seconds
+    fn machine() -> Machine {
+        Machine { velocity: [200.0; 3], acceleration: [10.0; 3], junction_deviation: 0.01, tool_change_time: 30.0 }
+    }
+
+    fn run(lines: &[&str]) -> Estimate {
+        let mut state = State::new();
+        let moves: Vec<Move> = lines.iter().enumerate().filter_map(|(i, line)| state.apply(i + 1, line)).collect();
+        estimate(&moves, &state, &machine())
+    }
+
+    fn round(seconds: f32) -> f32 {
+        (seconds * 100.0).round() / 100.0
+    }
+
+    #[test]
+    fn straight_moves_keep_their_speed_from_one_to_the_next() {
+        let one = run(&["G20 G90 G0 X0 Y0 Z0", "G1 X10 F60"]);
+        let two = run(&["G20 G90 G0 X0 Y0 Z0", "G1 X5 F60", "G1 X10"]);
+        assert_eq!(round(one.total), 10.1);
+        assert_eq!(round(two.total), 10.1);
+    }
+
+    #[test]
+    fn dwells_and_tool_changes_stop_the_machine_and_add_their_time() {
+        let estimate = run(&["G20 G90 G0 X0 Y0 Z0", "G1 X5 F60", "G4 P2", "G1 X10", "T2 M6"]);
+        assert_eq!((estimate.dwells, estimate.tool_changes), (1, 1));
+        assert_eq!(round(estimate.total), 5.1 + 2.0 + 5.1 + 30.0);
+        assert_eq!(check_time(&estimate, 0.5).status, Status::Warning);
+    }
+}
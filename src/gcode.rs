@@ -0,0 +1,312 @@
+use regex::Regex;
+use std::sync::Arc;
+use crate::{Point, Tool};
+
+lazy_static! {
+    static ref COMMENT_RE: Regex = Regex::new(r"\([^)]*\)|;.*").unwrap();
+    static ref WORD_RE: Regex = Regex::new(r"([A-Za-z])\s*([-+]?[0-9]*\.?[0-9]+)").unwrap();
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Motion {
+    Rapid,
+    Linear,
+    ArcCw,
+    ArcCcw,
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Spindle {
+    Off,
+    Cw,
+    Ccw,
+}
+
+/// a point in the program where the spindle should have been stopped
+#[derive(Clone,Debug)]
+pub struct Stop {
+    pub line: usize,
+    pub spindle: Spindle,
+}
+
+/// a pause in the program, in seconds
+#[derive(Clone,Debug)]
+pub struct Dwell {
+    pub line: usize,
+    pub seconds: f32,
+}
+
+/// the tool and how it's running. this rarely changes from one move to the next,
+/// so moves share a copy until it does
+#[derive(Clone,Debug,PartialEq)]
+pub struct Setup {
+    pub feed: Option<f32>,
+    pub spindle: Spindle,
+    pub rpm: Option<f32>,
+    pub tool: Tool,
+    pub tool_number: Option<u32>,
+    pub flutes: Option<u32>,
+}
+
+/// a single resolved move, with the modal state that was active when it ran
+#[derive(Clone,Debug,PartialEq)]
+pub struct Move {
+    pub line: usize,
+    pub motion: Motion,
+    pub from: Point,
+    pub to: Point,
+    /// xy center of an arc move
+    pub center: Option<(f32,f32)>,
+    pub setup: Arc<Setup>,
+}
+impl Move {
+    /// moves only along z, downwards
+    pub fn is_plunge(&self) -> bool {
+        self.from.x == self.to.x && self.from.y == self.to.y &&
+            matches!((self.from.z, self.to.z), (Some(a), Some(b)) if b < a)
+    }
+    /// feed moves below the top of the material
+    pub fn is_cutting(&self, thickness: Option<f32>) -> bool {
+        self.motion != Motion::Rapid && match (self.to.z, thickness) {
+            (Some(z), Some(t)) => z < t,
+            _ => true,
+        }
+    }
+    /// signed angle an arc sweeps through, positive counterclockwise
+    pub fn sweep(&self) -> Option<f32> {
+        let (cx, cy) = self.center?;
+        let (x0, y0, x1, y1) = (self.from.x?, self.from.y?, self.to.x?, self.to.y?);
+        let start = (y0 - cy).atan2(x0 - cx);
+        let mut sweep = (y1 - cy).atan2(x1 - cx) - start;
+        match self.motion {
+            Motion::ArcCcw => if sweep <= 0.0001 { sweep += std::f32::consts::TAU },
+            Motion::ArcCw => if sweep >= -0.0001 { sweep -= std::f32::consts::TAU },
+            _ => return None,
+        }
+        Some(sweep)
+    }
+    /// points along the move in xy, not including the start, with arcs split into short lines
+    pub fn xy_path(&self) -> Vec<(f32,f32)> {
+        let (x1, y1) = match (self.to.x, self.to.y) {
+            (Some(x), Some(y)) => (x, y),
+            _ => return Vec::new(),
+        };
+        if let (Some(sweep), Some((cx, cy)), Some(x0), Some(y0)) = (self.sweep(), self.center, self.from.x, self.from.y) {
+            let r = (x0 - cx).hypot(y0 - cy);
+            let start = (y0 - cy).atan2(x0 - cx);
+            let steps = ((sweep.abs() / 0.2).ceil() as usize).max(1);
+            let mut points: Vec<(f32,f32)> = (1..steps)
+                .map(|i| start + sweep * i as f32 / steps as f32)
+                .map(|a| (cx + r * a.cos(), cy + r * a.sin()))
+                .collect();
+            points.push((x1, y1));
+            points
+        } else {
+            vec![(x1, y1)]
+        }
+    }
+    /// distance travelled, following arcs
+    pub fn length(&self) -> f32 {
+        let dz = match (self.from.z, self.to.z) {
+            (Some(a), Some(b)) => b - a,
+            _ => 0.0,
+        };
+        let xy = match (self.sweep(), self.center, self.from.x, self.from.y, self.to.x, self.to.y) {
+            (Some(sweep), Some((cx, cy)), Some(x0), Some(y0), _, _) => sweep.abs() * (x0 - cx).hypot(y0 - cy),
+            (_, _, Some(x0), Some(y0), Some(x1), Some(y1)) => (x1 - x0).hypot(y1 - y0),
+            _ => 0.0,
+        };
+        xy.hypot(dz)
+    }
+    pub fn tool_label(&self) -> String {
+        tool_label(&self.setup.tool, self.setup.tool_number)
+    }
+}
+
+pub fn tool_label(tool: &Tool, tool_number: Option<u32>) -> String {
+    match tool_number {
+        Some(n) => format!("T{} ({})", n, tool),
+        None => format!("{}", tool),
+    }
+}
+
+/// letter/number pairs on a line, ignoring comments
+pub fn words(line: &str) -> Vec<(char,f32)> {
+    let code = COMMENT_RE.replace_all(line, " ");
+    WORD_RE.captures_iter(&code).filter_map(|c| {
+        let letter = c[1].chars().next()?.to_ascii_uppercase();
+        c[2].parse::<f32>().ok().map(|v| (letter, v))
+    }).collect()
+}
+
+/// modal state of the machine while walking through a program
+#[derive(Clone,Debug)]
+pub struct State {
+    pub pos: Point,
+    pub motion: Option<Motion>,
+    pub absolute: bool,
+    /// G21, so lengths and feeds in the program are converted from millimeters
+    pub metric: bool,
+    /// added to absolute positions, to move the program's origin
+    pub offset: [f32;3],
+    pub feed: Option<f32>,
+    pub spindle: Spindle,
+    pub rpm: Option<f32>,
+    pub tool: Tool,
+    pub tool_number: Option<u32>,
+    pub flutes: Option<u32>,
+    pub tool_changes: Vec<Stop>,
+    pub dwells: Vec<Dwell>,
+    pub end: Option<Stop>,
+    /// lines where the units (G20 or G21) and distance mode (G90 or G91) were first set
+    pub units_set: Option<usize>,
+    pub distance_mode_set: Option<usize>,
+    /// line of the last M5
+    pub spindle_stop: Option<usize>,
+    /// what the last move ran with, for the next one to share if nothing's changed
+    setup: Option<Arc<Setup>>,
+}
+impl State {
+    pub fn new() -> State {
+        State {
+            pos: Point::empty(),
+            motion: None,
+            absolute: true,
+            metric: false,
+            offset: [0.0;3],
+            feed: None,
+            spindle: Spindle::Off,
+            rpm: None,
+            tool: Tool::Unknown,
+            tool_number: None,
+            flutes: None,
+            tool_changes: Vec::new(),
+            dwells: Vec::new(),
+            end: None,
+            units_set: None,
+            distance_mode_set: None,
+            spindle_stop: None,
+            setup: None,
+        }
+    }
+
+    /// updates the modal state with one line of g-code, returning the move it made, if any
+    pub fn apply(&mut self, line_number: usize, line: &str) -> Option<Move> {
+        let words = words(line);
+        let mut axes = Point::empty();
+        let mut arc_offset: (Option<f32>,Option<f32>) = (None, None);
+        let mut radius: Option<f32> = None;
+        let mut dwell = false;
+        let mut p: Option<f32> = None;
+        let mut non_modal = false;
+        let mut feed: Option<f32> = None;
+        for (letter, value) in &words {
+            match letter {
+                'G' => match (value * 10.0).round() as i32 {
+                    0 => self.motion = Some(Motion::Rapid),
+                    10 => self.motion = Some(Motion::Linear),
+                    20 => self.motion = Some(Motion::ArcCw),
+                    30 => self.motion = Some(Motion::ArcCcw),
+                    800 => self.motion = None,
+                    200 | 210 => {
+                        self.metric = *value > 20.5;
+                        self.units_set = self.units_set.or(Some(line_number));
+                    }
+                    900 => { self.absolute = true; self.distance_mode_set = self.distance_mode_set.or(Some(line_number)); }
+                    910 => { self.absolute = false; self.distance_mode_set = self.distance_mode_set.or(Some(line_number)); }
+                    //p is in seconds, as in mach3's default setup
+                    40 => dwell = true,
+                    //axis words on these lines are not a normal move
+                    100 | 280 | 300 | 530 | 920 => non_modal = true,
+                    _ => {}
+                },
+                'M' => match *value as i32 {
+                    2 | 30 => self.end = Some(Stop { line: line_number, spindle: self.spindle }),
+                    3 => self.spindle = Spindle::Cw,
+                    4 => self.spindle = Spindle::Ccw,
+                    5 => { self.spindle = Spindle::Off; self.spindle_stop = Some(line_number); }
+                    6 => self.tool_changes.push(Stop { line: line_number, spindle: self.spindle }),
+                    _ => {}
+                },
+                'F' => feed = Some(*value),
+                'S' => self.rpm = Some(*value),
+                'T' => self.tool_number = Some(*value as u32),
+                'X' => axes.x = Some(*value),
+                'Y' => axes.y = Some(*value),
+                'Z' => axes.z = Some(*value),
+                'I' => arc_offset.0 = Some(*value),
+                'J' => arc_offset.1 = Some(*value),
+                'R' => radius = Some(*value),
+                'P' => p = Some(*value),
+                _ => {}
+            }
+        }
+        //everything is kept in inches, whatever units the program is in
+        let scale = if self.metric { 1.0 / 25.4 } else { 1.0 };
+        for i in 0..3 {
+            axes[i] = axes[i].map(|v| v * scale);
+        }
+        arc_offset = (arc_offset.0.map(|v| v * scale), arc_offset.1.map(|v| v * scale));
+        radius = radius.map(|r| r * scale);
+        if let Some(feed) = feed {
+            self.feed = Some(feed * scale);
+        }
+        if dwell {
+            self.dwells.push(Dwell { line: line_number, seconds: p.unwrap_or(0.0) });
+        }
+        if axes.is_empty() || non_modal {
+            return None;
+        }
+        let motion = self.motion?;
+        let from = self.pos;
+        for i in 0..3 {
+            if let Some(v) = axes[i] {
+                self.pos[i] = if self.absolute { Some(v + self.offset[i]) } else { Some(self.pos[i].unwrap_or(0.0) + v) };
+            }
+        }
+        let center = match (motion, from.x, from.y, self.pos.x, self.pos.y) {
+            (Motion::ArcCw | Motion::ArcCcw, Some(x0), Some(y0), Some(x1), Some(y1)) => {
+                if arc_offset.0.is_some() || arc_offset.1.is_some() {
+                    Some((x0 + arc_offset.0.unwrap_or(0.0), y0 + arc_offset.1.unwrap_or(0.0)))
+                } else {
+                    radius.map(|r| arc_center((x0, y0), (x1, y1), r, motion == Motion::ArcCw))
+                }
+            }
+            _ => None,
+        };
+        let setup = Setup {
+            feed: self.feed,
+            spindle: self.spindle,
+            rpm: self.rpm,
+            tool: self.tool.clone(),
+            tool_number: self.tool_number,
+            flutes: self.flutes,
+        };
+        let setup = match &self.setup {
+            Some(last) if **last == setup => last.clone(),
+            _ => self.setup.insert(Arc::new(setup)).clone(),
+        };
+        Some(Move {
+            line: line_number,
+            motion,
+            from,
+            to: self.pos,
+            center,
+            setup,
+        })
+    }
+}
+
+/// center of an arc given by its radius. a negative radius means the long way around
+fn arc_center(from: (f32,f32), to: (f32,f32), r: f32, clockwise: bool) -> (f32,f32) {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let chord = dx.hypot(dy);
+    let mid = (from.0 + dx / 2.0, from.1 + dy / 2.0);
+    if chord < 0.000001 {
+        return mid;
+    }
+    let h = (r * r - chord * chord / 4.0).max(0.0).sqrt();
+    //the center is to the right of the chord for a short clockwise arc
+    let side = if clockwise == (r > 0.0) { -1.0 } else { 1.0 };
+    (mid.0 - side * h * dy / chord, mid.1 + side * h * dx / chord)
+}
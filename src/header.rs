@@ -0,0 +1,256 @@
+use regex::Regex;
+use crate::gcode;
+use crate::Point;
+
+lazy_static! {
+    static ref COMMENT_RE: Regex = Regex::new(r"\(([^)]*)\)").unwrap();
+    static ref AXIS_RE: [Regex;3] = [
+        Regex::new(r"(?:^|[^A-Za-z])X\s*[=:]?\s*(-?[0-9]*\.?[0-9]+)").unwrap(),
+        Regex::new(r"(?:^|[^A-Za-z])Y\s*[=:]?\s*(-?[0-9]*\.?[0-9]+)").unwrap(),
+        Regex::new(r"(?:^|[^A-Za-z])Z\s*[=:]?\s*(-?[0-9]*\.?[0-9]+)").unwrap(),
+    ];
+    static ref THICKNESS_RE: Regex = Regex::new(r"(?i)(?:material|stock) thickness\s*[=:]?\s*(-?[0-9]*\.?[0-9]+)").unwrap();
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Units {
+    Inch,
+    Mm,
+}
+
+/// where z zero is on the stock
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum ZOrigin {
+    Top,
+    Bottom,
+}
+
+/// where xy zero is on the stock
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum XyOrigin {
+    Corner,
+    Center,
+}
+
+/// what the program header says about the stock, in inches
+#[derive(Clone,Debug)]
+pub struct Stock {
+    pub size: Point,
+    pub z_origin: Option<ZOrigin>,
+    pub xy_origin: Option<XyOrigin>,
+    pub units: Option<Units>,
+    /// which recognizer found it
+    pub source: &'static str,
+}
+impl Stock {
+    fn new(source: &'static str) -> Stock {
+        Stock { size: Point::empty(), z_origin: None, xy_origin: None, units: None, source }
+    }
+}
+
+/// the header is everything before the first line that moves an axis
+pub fn ends_header(line: &str) -> bool {
+    let code = COMMENT_RE.replace_all(line, "");
+    code.contains('X') || code.contains('Y') || code.contains('Z')
+}
+
+/// the text of every comment before the first move
+fn header_comments(contents: &str) -> Vec<String> {
+    let mut comments = Vec::new();
+    for line in contents.lines() {
+        if ends_header(line) {
+            break;
+        }
+        comments.extend(COMMENT_RE.captures_iter(line).map(|c| c[1].trim().to_string()));
+    }
+    comments
+}
+
+fn axes(text: &str) -> Point {
+    let mut point = Point::empty();
+    for i in 0..3 {
+        point[i] = AXIS_RE[i].captures(text).and_then(|c| c[1].parse::<f32>().ok());
+    }
+    point
+}
+
+fn find<'a>(comments: &'a [String], keywords: &[&str]) -> Option<&'a String> {
+    comments.iter().find(|c| {
+        let lower = c.to_lowercase();
+        keywords.iter().all(|k| lower.contains(k))
+    })
+}
+
+fn units(comments: &[String]) -> Option<Units> {
+    let lower = find(comments, &["units"])?.to_lowercase();
+    if lower.contains("mm") || lower.contains("millimet") {
+        Some(Units::Mm)
+    } else if lower.contains("inch") {
+        Some(Units::Inch)
+    } else {
+        None
+    }
+}
+
+/// origins written out in words, like vcarve's `( Z Origin for Material = Material Surface)`
+fn worded_origins(comments: &[String], stock: &mut Stock) {
+    for c in comments.iter().map(|c| c.to_lowercase()) {
+        if c.contains("z origin") || c.contains("z zero") {
+            if c.contains("surface") || c.contains("top") {
+                stock.z_origin = Some(ZOrigin::Top);
+            } else if c.contains("bed") || c.contains("table") || c.contains("bottom") {
+                stock.z_origin = Some(ZOrigin::Bottom);
+            }
+        }
+        if c.contains("xy origin") || c.contains("xy zero") {
+            if c.contains("center") || c.contains("centre") {
+                stock.xy_origin = Some(XyOrigin::Center);
+            } else if c.contains("corner") || c.contains("left") {
+                stock.xy_origin = Some(XyOrigin::Corner);
+            }
+        }
+    }
+}
+
+/// fusion 360: `(STOCK LOWER: X0 Y0 Z-0.25)` and `(STOCK UPPER: X12 Y10 Z0)`
+fn fusion(comments: &[String]) -> Option<Stock> {
+    let lower = axes(find(comments, &["stock", "lower"]).or_else(|| find(comments, &["stock", "min"]))?);
+    let upper = axes(find(comments, &["stock", "upper"]).or_else(|| find(comments, &["stock", "max"]))?);
+    let mut stock = Stock::new("Fusion");
+    for i in 0..3 {
+        stock.size[i] = upper[i].zip(lower[i]).map(|(u,l)| u - l);
+    }
+    let near = |a: Option<f32>, b: f32| a.is_some_and(|a| (a - b).abs() < 0.001);
+    if near(upper.z, 0.0) {
+        stock.z_origin = Some(ZOrigin::Top);
+    } else if near(lower.z, 0.0) {
+        stock.z_origin = Some(ZOrigin::Bottom);
+    }
+    if near(lower.x, 0.0) && near(lower.y, 0.0) {
+        stock.xy_origin = Some(XyOrigin::Corner);
+    } else if let (Some(lx), Some(ly), Some(ux), Some(uy)) = (lower.x, lower.y, upper.x, upper.y) {
+        if (lx + ux).abs() < 0.001 && (ly + uy).abs() < 0.001 {
+            stock.xy_origin = Some(XyOrigin::Center);
+        }
+    }
+    stock.units = units(comments);
+    Some(stock)
+}
+
+/// vcarve: `( Material Size)` followed by `( X= 24.000, Y= 12.000, Z= 0.750)`
+fn vcarve(comments: &[String]) -> Option<Stock> {
+    let i = comments.iter().position(|c| c.to_lowercase().contains("material size"))?;
+    let size = comments[i..].iter().take(2).map(|c| axes(c)).find(|p| p.z.is_some())?;
+    let mut stock = Stock::new("VCarve");
+    stock.size = size;
+    worded_origins(comments, &mut stock);
+    stock.units = units(comments);
+    Some(stock)
+}
+
+/// mach3 wizards: `(Mach3 Wizard ...)` with `(Material Thickness: 0.25)`
+fn mach3_wizard(comments: &[String]) -> Option<Stock> {
+    find(comments, &["wizard"])?;
+    let mut stock = Stock::new("Mach3 Wizard");
+    stock.size.z = comments.iter().find_map(|c| THICKNESS_RE.captures(c)).and_then(|c| c[1].parse::<f32>().ok());
+    stock.size.z?;
+    worded_origins(comments, &mut stock);
+    stock.units = units(comments);
+    Some(stock)
+}
+
+/// sheetcam: `(Post processor: Mach3 mill.scpost)` with `(Material: Aluminum X=12.0 Y=10.0 Z=0.25)`
+fn sheetcam(comments: &[String]) -> Option<Stock> {
+    find(comments, &["post processor"])?;
+    let size = comments.iter().filter(|c| c.to_lowercase().contains("material"))
+        .map(|c| axes(c)).find(|p| p.z.is_some())?;
+    let mut stock = Stock::new("SheetCam");
+    stock.size = size;
+    worded_origins(comments, &mut stock);
+    stock.units = units(comments);
+    Some(stock)
+}
+
+/// anything else with a material or stock comment giving its size
+fn generic(comments: &[String]) -> Option<Stock> {
+    let size = comments.iter()
+        .filter(|c| { let lower = c.to_lowercase(); lower.contains("material") || lower.contains("stock") })
+        .map(|c| axes(c)).find(|p| p.z.is_some())?;
+    let mut stock = Stock::new("generic");
+    stock.size = size;
+    worded_origins(comments, &mut stock);
+    stock.units = units(comments);
+    Some(stock)
+}
+
+type Recognizer = fn(&[String]) -> Option<Stock>;
+
+/// tried in order, so the more specific formats come first
+const RECOGNIZERS: [Recognizer; 5] = [fusion, vcarve, mach3_wizard, sheetcam, generic];
+
+/// the units a line picks with G20 or G21, if it does
+pub fn program_units(line: &str) -> Option<Units> {
+    gcode::words(line).iter().find_map(|(letter, value)| match (letter, (value * 10.0).round() as i32) {
+        ('G', 200) => Some(Units::Inch),
+        ('G', 210) => Some(Units::Mm),
+        _ => None,
+    })
+}
+
+/// reads the stock size from the header, converted to inches. the header is in the units the program
+/// picks with G20 or G21, or the ones a units comment names if it doesn't pick any
+pub fn parse(contents: &str, program_units: Option<Units>) -> Option<Stock> {
+    let comments = header_comments(contents);
+    let mut stock = RECOGNIZERS.iter().find_map(|r| r(&comments))?;
+    stock.units = program_units.or(stock.units);
+    if stock.units == Some(Units::Mm) {
+        for i in 0..3 {
+            stock.size[i] = stock.size[i].map(|v| v / 25.4);
+        }
+    }
+    Some(stock)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size(stock: &Stock) -> [Option<f32>;3] {
+        [0, 1, 2].map(|i| stock.size[i].map(|v| (v * 1000.0).round() / 1000.0))
+    }
+
+    #[test]
+    fn fusion_stock_corners_give_the_size_and_origins() {
+        let stock = parse("(STOCK LOWER: X0 Y0 Z-0.25)\n(STOCK UPPER: X12 Y10 Z0)\nG0 Z1\n", None).unwrap();
+        assert_eq!(stock.source, "Fusion");
+        assert_eq!(size(&stock), [Some(12.0), Some(10.0), Some(0.25)]);
+        assert_eq!((stock.z_origin, stock.xy_origin), (Some(ZOrigin::Top), Some(XyOrigin::Corner)));
+    }
+
+    #[test]
+    fn vcarve_material_size_in_millimeters_is_converted() {
+        let header = "( Material Size)\n( X= 300.000, Y= 200.000, Z= 12.700)\n( Z Origin for Material = Material Surface)\n\
+            ( XY Origin = Center)\n( Units = mm )\nG0 Z10\n";
+        let stock = parse(header, None).unwrap();
+        assert_eq!(stock.source, "VCarve");
+        assert_eq!(size(&stock), [Some(11.811), Some(7.874), Some(0.5)]);
+        assert_eq!((stock.z_origin, stock.xy_origin, stock.units), (Some(ZOrigin::Top), Some(XyOrigin::Center), Some(Units::Mm)));
+    }
+
+    #[test]
+    fn the_program_units_win_over_a_units_comment() {
+        let header = "(Post processor: Mach3 mill.scpost)\n(Units: mm)\n(Material: Aluminum X=12.0 Y=10.0 Z=0.25)\nG20\nG0 Z1\n";
+        let stock = parse(header, Some(Units::Inch)).unwrap();
+        assert_eq!(stock.source, "SheetCam");
+        assert_eq!(size(&stock), [Some(12.0), Some(10.0), Some(0.25)]);
+    }
+
+    #[test]
+    fn a_wizard_header_only_gives_the_thickness() {
+        let stock = parse("(Mach3 Wizard Pocket)\n(Material Thickness: 0.75)\n(Z Zero = Table)\nG0 Z1\n", None).unwrap();
+        assert_eq!(stock.source, "Mach3 Wizard");
+        assert_eq!(size(&stock), [None, None, Some(0.75)]);
+        assert_eq!(stock.z_origin, Some(ZOrigin::Bottom));
+        assert!(parse("(no stock here)\nG0 Z1\n", None).is_none());
+    }
+}
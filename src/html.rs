@@ -0,0 +1,63 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use crate::{Outcome, Status};
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn color(status: &Status) -> &'static str {
+    match status {
+        Status::Pass => "#2a2",
+        Status::Warning => "#c90",
+        Status::Suppressed | Status::Skipped => "#888",
+        _ => "#d22",
+    }
+}
+
+/// a standalone page with the outcomes for each file, worst first like the terminal report
+pub fn report(files: &[(&Path, &[Outcome])]) -> String {
+    let mut body = String::new();
+    for (path, outcomes) in files {
+        writeln!(body, "<h2>{}</h2>",escape(&path.display().to_string())).unwrap();
+        let mut sorted: Vec<&Outcome> = outcomes.iter().collect();
+        sorted.sort_by_key(|o| match o.status { Status::Fail => 0, Status::Warning => 1, Status::Error => 2, Status::Skipped => 3, Status::Suppressed => 4, Status::Pass => 5 });
+        for outcome in sorted {
+            let mut lines = outcome.message.lines();
+            writeln!(body, "<div class=\"outcome\"><b style=\"color:{}\">[{}]</b> <b>{}</b>: {}",
+                color(&outcome.status), outcome.status, escape(&outcome.name), escape(lines.next().unwrap_or(""))).unwrap();
+            let details: Vec<String> = lines.map(|l| format!("<li>{}</li>",escape(l))).collect();
+            if !details.is_empty() {
+                writeln!(body, "<ul>{}</ul>",details.join("")).unwrap();
+            }
+            writeln!(body, "</div>").unwrap();
+        }
+    }
+    format!("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Validate Toolpath</title>\n\
+        <style>body {{ font-family: sans-serif; }} .outcome {{ margin: 0.5em 0; }} ul {{ margin: 0.2em 0; }}</style>\n\
+        </head><body>\n<h1>Validate Toolpath</h1>\n{}</body></html>\n",body)
+}
+
+pub fn write(path: &Path, html: &str) -> Result<(),String> {
+    fs::write(path, html).map_err(|_| format!("couldn't write report '{}'",path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outcomes_are_listed_worst_first_and_escaped() {
+        let outcomes = [
+            Outcome::new_full("Depth", Status::Pass, "cuts through".into()),
+            Outcome::new_full("Spindle", Status::Fail, "spindle <off>\nfirst at line 3".into()),
+            Outcome::new_full("Feed Rate", Status::Warning, "too fast".into()),
+        ];
+        let page = report(&[(Path::new("a&b.nc"), &outcomes)]);
+        assert!(page.contains("<h2>a&amp;b.nc</h2>"));
+        let at = |name: &str| page.find(&format!("<b>{}</b>",name)).unwrap();
+        assert!(at("Spindle") < at("Feed Rate") && at("Feed Rate") < at("Depth"));
+        assert!(page.contains("<b>Spindle</b>: spindle &lt;off&gt;\n<ul><li>first at line 3</li></ul>"));
+    }
+}
@@ -0,0 +1,191 @@
+use std::fs;
+use std::path::Path;
+
+const AXES: [char; 3] = ['X', 'Y', 'Z'];
+
+/// a value for the machine section of config.toml, and where it came from in the profile
+pub struct Imported {
+    pub name: String,
+    pub value: f32,
+    pub source: String,
+}
+
+/// how far each axis can go and how fast, in the profile's own units
+#[derive(Default)]
+struct Axis {
+    limits: Option<(f32,f32,String)>,
+    /// per minute
+    velocity: Option<(f32,String)>,
+    /// per second squared
+    acceleration: Option<(f32,String)>,
+    /// whether this axis is in millimeters when it's set apart from the rest of the profile, and where that's set
+    millimeters: Option<(bool,String)>,
+}
+
+/// reads the soft limits, velocities and accelerations from a mach3 `.xml` or linuxcnc `.ini` machine profile,
+/// along with notes on the units and anything the profile was missing
+pub fn read(path: &Path) -> Result<(Vec<Imported>,Vec<String>),String> {
+    let contents = fs::read_to_string(path).map_err(|_| format!("couldn't read machine profile '{}'",path.display()))?;
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+    let (axes, millimeters, mut notes) = match extension.as_deref() {
+        Some("xml") => mach3(&contents),
+        Some("ini") => linuxcnc(&contents),
+        _ => return Err(format!("'{}' isn't a mach3 .xml or linuxcnc .ini profile",path.display())),
+    };
+    let mut imported: Vec<Imported> = Vec::new();
+    if millimeters {
+        notes.insert(0, "the profile is in millimeters, converted to inches".into());
+    }
+    let mut push = |name: String, value: f32, source: &str| imported.push(Imported { name, value, source: source.into() });
+    for (axis, found) in AXES.iter().zip(&axes) {
+        let lower = axis.to_ascii_lowercase();
+        let millimeters = match &found.millimeters {
+            Some((axis_millimeters, source)) if *axis_millimeters != millimeters => {
+                notes.push(format!("{} is in {} by {}",lower,if *axis_millimeters { "millimeters, converted to inches" } else { "inches" },source));
+                *axis_millimeters
+            }
+            _ => millimeters,
+        };
+        let scale = if millimeters { 1.0 / 25.4 } else { 1.0 };
+        match &found.limits {
+            Some((min, max, source)) => {
+                push(format!("SOFT_MIN_{}",axis), min * scale, source);
+                push(format!("SOFT_MAX_{}",axis), max * scale, source);
+            }
+            None => notes.push(format!("the profile has no soft limits for {}, they're left as they were",lower)),
+        }
+        match &found.velocity {
+            Some((velocity, source)) => push(format!("VELOCITY_{}",axis), velocity * scale, source),
+            None => notes.push(format!("the profile has no velocity for {}, it's left as it was",lower)),
+        }
+        match &found.acceleration {
+            Some((acceleration, source)) => push(format!("ACCELERATION_{}",axis), acceleration * scale, source),
+            None => notes.push(format!("the profile has no acceleration for {}, it's left as it was",lower)),
+        }
+    }
+    if imported.is_empty() {
+        return Err(format!("couldn't find any axis limits in '{}'",path.display()));
+    }
+    Ok((imported, notes))
+}
+
+/// the text inside the first `<name>...</name>` element
+fn element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{}>",name))? + name.len() + 2;
+    let end = xml[start..].find(&format!("</{}>",name))? + start;
+    Some(xml[start..end].trim())
+}
+
+fn number(xml: &str, name: &str) -> Option<f32> {
+    element(xml, name)?.parse().ok()
+}
+
+/// mach3 keeps soft limits in its preferences as `SoftMin0`, `SoftMax0`... and motor tuning in `Motor0`...,
+/// with velocities per minute and accelerations per second squared
+fn mach3(xml: &str) -> ([Axis;3],bool,Vec<String>) {
+    let millimeters = element(xml, "Units").is_some_and(|u| u.to_lowercase().starts_with("mm") || u.to_lowercase().starts_with("metric"));
+    let axes = [0, 1, 2].map(|i| {
+        let mut axis = Axis::default();
+        if let (Some(min), Some(max)) = (number(xml, &format!("SoftMin{}",i)), number(xml, &format!("SoftMax{}",i))) {
+            axis.limits = Some((min, max, format!("SoftMin{} and SoftMax{}",i,i)));
+        }
+        if let Some(motor) = element(xml, &format!("Motor{}",i)) {
+            let motor_value = |names: &[&str]| names.iter().find_map(|n| number(motor, n).map(|v| (v, format!("Motor{} {}",i,n))));
+            axis.velocity = motor_value(&["Vel", "Velocity"]);
+            axis.acceleration = motor_value(&["Acc", "Accel", "Acceleration"]);
+        }
+        axis
+    });
+    (axes, millimeters, Vec::new())
+}
+
+/// the `KEY = value` pairs in each `[SECTION]` of an ini file
+fn ini_sections(ini: &str) -> Vec<(String,Vec<(String,String)>)> {
+    let mut sections: Vec<(String,Vec<(String,String)>)> = Vec::new();
+    for line in ini.lines().map(str::trim).filter(|l| !l.starts_with(['#', ';'])) {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push((name.trim().to_uppercase(), Vec::new()));
+        } else if let (Some((key, value)), Some((_, values))) = (line.split_once('='), sections.last_mut()) {
+            values.push((key.trim().to_uppercase(), value.trim().to_string()));
+        }
+    }
+    sections
+}
+
+/// linuxcnc units are a name like `mm` or `inch`, or in older versions how many of them make a millimeter
+fn ini_millimeters(units: &str) -> Option<bool> {
+    match units.to_lowercase().as_str() {
+        "mm" | "metric" | "millimeter" | "millimeters" => Some(true),
+        "in" | "inch" | "inches" | "imperial" => Some(false),
+        number => number.parse::<f32>().ok().and_then(|n| {
+            if (n - 1.0).abs() < 0.001 { Some(true) } else if (n - 1.0 / 25.4).abs() < 0.0001 { Some(false) } else { None }
+        }),
+    }
+}
+
+/// linuxcnc has an `[AXIS_X]` section for each axis, or `[AXIS_0]` in older versions, with velocities per second
+/// and `LINEAR_UNITS` in `[TRAJ]`. since 2.8 the limits can be on the `[JOINT_0]`... sections instead, which are taken
+/// to be x, y and z in order. an axis or joint can have its own `UNITS`
+fn linuxcnc(ini: &str) -> ([Axis;3],bool,Vec<String>) {
+    let sections = ini_sections(ini);
+    let get = |section: &str, key: &str| sections.iter().filter(|(s, _)| s == section)
+        .find_map(|(_, values)| values.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str()));
+    let mut notes: Vec<String> = Vec::new();
+    let traj = get("TRAJ", "LINEAR_UNITS");
+    let millimeters = traj.and_then(ini_millimeters).unwrap_or(false);
+    if let Some(units) = traj.filter(|u| ini_millimeters(u).is_none()) {
+        notes.push(format!("couldn't tell what [TRAJ] LINEAR_UNITS = {} is, it's taken to be inches",units));
+    }
+    let axes = [0, 1, 2].map(|i| {
+        let mut axis = Axis::default();
+        let found: Vec<String> = [format!("AXIS_{}",AXES[i]), format!("AXIS_{}",i), format!("JOINT_{}",i)].into_iter()
+            .filter(|s| sections.iter().any(|(n, _)| n == s))
+            .collect();
+        //the first section that has a value, so an axis section can leave some to its joint
+        let value = |key: &str| found.iter().find_map(|section| get(section, key).and_then(|v| v.parse::<f32>().ok()).map(|v| (v, section)));
+        if let (Some((min, section)), Some((max, _))) = (value("MIN_LIMIT"), value("MAX_LIMIT")) {
+            axis.limits = Some((min, max, format!("[{}] MIN_LIMIT and MAX_LIMIT",section)));
+        }
+        axis.velocity = value("MAX_VELOCITY").map(|(v, section)| (v * 60.0, format!("[{}] MAX_VELOCITY",section)));
+        axis.acceleration = value("MAX_ACCELERATION").map(|(a, section)| (a, format!("[{}] MAX_ACCELERATION",section)));
+        if let Some((section, units)) = found.iter().find_map(|section| get(section, "UNITS").map(|u| (section, u))) {
+            match ini_millimeters(units) {
+                Some(m) => axis.millimeters = Some((m, format!("[{}] UNITS",section))),
+                None => notes.push(format!("couldn't tell what [{}] UNITS = {} is, it's left out",section,units)),
+            }
+        }
+        axis
+    });
+    //steps per unit are for driving the motors, the limits are already in machine units
+    if sections.iter().any(|(_, values)| values.iter().any(|(k, _)| k == "SCALE" || k == "INPUT_SCALE")) {
+        notes.push("the profile's step scales aren't needed, its limits are already in machine units".into());
+    }
+    (axes, millimeters, notes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linuxcnc_joints_fill_in_for_axes_with_their_own_units() {
+        let ini = "[TRAJ]\nLINEAR_UNITS = inch\n\n[AXIS_X]\nMIN_LIMIT = -1\nMAX_LIMIT = 24\n\n[JOINT_0]\nMAX_VELOCITY = 2\nMAX_ACCELERATION = 10\nSCALE = 2000\n\n\
+            [JOINT_2]\nUNITS = mm\nMIN_LIMIT = -101.6\nMAX_LIMIT = 0\n";
+        let (axes, millimeters, notes) = linuxcnc(ini);
+        assert!(!millimeters);
+        assert_eq!(axes[0].limits, Some((-1.0, 24.0, "[AXIS_X] MIN_LIMIT and MAX_LIMIT".into())));
+        assert_eq!(axes[0].velocity, Some((120.0, "[JOINT_0] MAX_VELOCITY".into())));
+        assert_eq!(axes[2].millimeters, Some((true, "[JOINT_2] UNITS".into())));
+        assert_eq!(notes, vec!["the profile's step scales aren't needed, its limits are already in machine units"]);
+    }
+
+    #[test]
+    fn an_axis_in_millimeters_is_converted_on_its_own() {
+        let path = std::env::temp_dir().join(format!("validate_toolpath_import_{}.ini",std::process::id()));
+        fs::write(&path, "[TRAJ]\nLINEAR_UNITS = 0.03937\n[AXIS_Z]\nUNITS = 1.0\nMIN_LIMIT = -101.6\nMAX_LIMIT = 0\n").unwrap();
+        let (imported, notes) = read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(imported.iter().map(|i| (i.name.as_str(), i.value)).collect::<Vec<_>>(), vec![("SOFT_MIN_Z", -4.0), ("SOFT_MAX_Z", 0.0)]);
+        assert!(notes.contains(&"z is in millimeters, converted to inches by [AXIS_Z] UNITS".to_string()));
+    }
+}
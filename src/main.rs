@@ -3,58 +3,190 @@ extern crate lazy_static;
 use regex::Regex;
 use std::ops;
 use std::fmt;
-use std::env;
 use std::fs;
 use std::fs::File;
-use std::path::PathBuf;
+use std::path::{Path,PathBuf};
 use std::collections::{HashMap,HashSet};
 use native_dialog::{FileDialog};
-use std::io::Write;
+use std::io::{BufRead, BufReader, Write};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
+mod args;
 mod config;
+mod diff;
+mod directives;
+mod estimate;
+mod gcode;
+mod header;
+mod html;
+mod import;
+mod passes;
+mod wear;
+mod profiles;
+mod reference;
+mod render;
 
 fn main() {
     println!("Validate Toolpath v1.2");
     println!("Utility to catch stupid toolpath mistakes");
     println!("https://github.com/mileskerr/validate_toolpath");
 
-    let config_items = config::read_config();
+    let args = match args::parse() {
+        Ok(args) => { args }
+        Err(error) => { eprintln!("Error: {}",error); return; }
+    };
+    if let Some(dir) = &args.watch {
+        watch(dir, &args);
+    }
+    if let Some(profile) = &args.import {
+        std::process::exit(import_machine(profile, &args));
+    }
+    if let Some((old_path, new_path)) = &args.diff {
+        let Some(config) = read_config(&args, new_path.parent()) else { return };
+        let results = match compare(old_path, new_path, &config, &args) {
+            Ok(results) => { results }
+            Err(error) => { eprintln!("Error: {}",error); return; }
+        };
+        print_results(&results, true);
+        loop { std::thread::park(); }
+    }
+
+    let paths = match args::expand(&args.paths) {
+        Ok(paths) => { paths }
+        Err(error) => { eprintln!("Error: {}",error); std::process::exit(2); }
+    };
+    if paths != args.paths || paths.len() > 1 {
+        std::process::exit(batch(&paths, &args));
+    }
 
     println!("---");
     println!("Please select a file...");
 
-    let path = match get_path() {
+    let path = match get_path(paths.first().cloned()) {
         Ok(path) => { path }
         Err(error) => { eprintln!("Error: {}",error); return; }
     };
-    let contents = match get_file(path.clone()) {
-        Ok(file) => { file }
+    let Some(config) = read_config(&args, path.parent()) else { return };
+
+    println!("Validating file \'{}\'...",path.display());
+    let report = match check(&path,&config,&args) {
+        Ok(report) => { report }
         Err(error) => { eprintln!("Error: {}",error); return; }
     };
+    print_results(&report.outcomes, true);
+    write_html(&args, &[(&path, &report.outcomes)]);
+    loop { std::thread::park(); }
+}
 
-    println!("Validating file \'{}\'...",path.display());
-    let results = check(&contents,&config_items);
+/// the config for toolpaths in `dir`, printing any errors in it
+fn read_config(args: &args::Args, dir: Option<&Path>) -> Option<config::Config> {
+    match config::read_config(args, dir) {
+        Ok(config) => Some(config),
+        Err(errors) => {
+            for error in errors.lines() {
+                eprintln!("Error: {}",error);
+            }
+            None
+        }
+    }
+}
+
+fn write_html(args: &args::Args, files: &[(&Path, &[Outcome])]) {
+    if let Some(html) = &args.html {
+        if let Err(error) = html::write(html, &html::report(files)) {
+            eprintln!("Error: {}",error);
+        }
+    }
+}
+
+/// validates toolpaths in a directory whenever they're written or updated, until the program is closed
+fn watch(dir: &Path, args: &args::Args) -> ! {
+    let snapshot = || -> Result<HashMap<PathBuf,Stamp>,String> {
+        Ok(args::toolpaths(dir, "*")?.into_iter()
+            .filter_map(|p| fs::metadata(&p).ok().map(|m| (p, (m.modified().ok(), m.len()))))
+            .collect())
+    };
+    let mut seen = match snapshot() {
+        Ok(seen) => { seen }
+        Err(error) => { eprintln!("Error: {}",error); std::process::exit(2); }
+    };
+    println!("---");
+    println!("Watching \'{}\' for new or updated toolpaths...",dir.display());
+    println!("press Ctrl-C to exit");
+    let mut pending: HashSet<PathBuf> = HashSet::new();
+    loop {
+        std::thread::sleep(Duration::from_secs(1));
+        let now = snapshot().unwrap_or_default();
+        let ready = settled(&seen, &now, &mut pending);
+        //read for every change, so edits to the config are picked up too
+        let config = if ready.is_empty() { None } else { read_config(args, Some(dir)) };
+        for path in ready.iter().filter(|_| config.is_some()) {
+            println!("---");
+            println!("Validating file \'{}\'...",path.display());
+            //a file that can't be read now may be fixed by the next save, so keep watching
+            let report = match check(path, config.as_ref().unwrap(), args) {
+                Ok(report) => { report }
+                Err(error) => { eprintln!("Error: {}",error); continue; }
+            };
+            print_results(&report.outcomes, false);
+            write_html(args, &[(path, &report.outcomes)]);
+        }
+        seen = now;
+    }
+}
+
+/// how a file looked the last time the watched directory was listed: when it was modified and how big it was
+type Stamp = (Option<SystemTime>,u64);
+
+/// the files that changed since the last listing but not since this one, sorted by name. files that are changing
+/// are kept in `pending` until they stop, so they aren't read half written
+fn settled(seen: &HashMap<PathBuf,Stamp>, now: &HashMap<PathBuf,Stamp>, pending: &mut HashSet<PathBuf>) -> Vec<PathBuf> {
+    let mut ready: Vec<PathBuf> = Vec::new();
+    for (path, stamp) in now {
+        if seen.get(path) != Some(stamp) {
+            pending.insert(path.clone());
+        } else if pending.remove(path) {
+            ready.push(path.clone());
+        }
+    }
+    pending.retain(|p| now.contains_key(p));
+    ready.sort();
+    ready
+}
 
-    let passed: Vec<Outcome> = results.clone().into_iter().filter(|r| r.status == Status::Pass).collect();
-    let failed: Vec<Outcome> = results.clone().into_iter().filter(|r| r.status == Status::Fail).collect();
-    let warnings: Vec<Outcome> = results.clone().into_iter().filter(|r| r.status == Status::Warning).collect();
-    let errors: Vec<Outcome> = results.clone().into_iter().filter(|r| r.status == Status::Error).collect();
+/// prints the outcomes worst first, under a one line summary
+fn print_results(results: &[Outcome], exit_hint: bool) {
+    let passed: Vec<&Outcome> = results.iter().filter(|r| r.status == Status::Pass).collect();
+    let failed: Vec<&Outcome> = results.iter().filter(|r| r.status == Status::Fail).collect();
+    let warnings: Vec<&Outcome> = results.iter().filter(|r| r.status == Status::Warning).collect();
+    let errors: Vec<&Outcome> = results.iter().filter(|r| r.status == Status::Error).collect();
+    let skipped: Vec<&Outcome> = results.iter().filter(|r| r.status == Status::Skipped).collect();
+    let suppressed: Vec<&Outcome> = results.iter().filter(|r| r.status == Status::Suppressed).collect();
+    let mut suppressed_s = if skipped.is_empty() { String::new() } else { format!(", {} skipped",skipped.len()) };
+    if !suppressed.is_empty() {
+        suppressed_s += &format!(", {} suppressed",suppressed.len());
+    }
 
     println!("---");
     let mut stdout = StandardStream::stdout(ColorChoice::Always);
+    //a skipped check didn't find anything wrong, so it doesn't stop the program passing
     if (failed.len() + warnings.len() + errors.len()) == 0 {
         stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green))).unwrap();
-        println!("{}","SUCCESS! All checks passed")
+        println!("SUCCESS! All checks passed{}",suppressed_s)
     } else {
         stdout.set_color(ColorSpec::new().set_fg(Some(Color::Blue))).unwrap();
         let warning_s = if warnings.len() == 1 { "warning" } else { "warnings" };
         let error_s = if errors.len() == 1 { "error" } else { "errors" };
-        println!("COMPLETE: {} passed, {} failed, {} {warning_s}, {} {error_s} ", passed.len(), failed.len(), warnings.len(), errors.len())
+        println!("COMPLETE: {} passed, {} failed, {} {warning_s}, {} {error_s}{} ", passed.len(), failed.len(), warnings.len(), errors.len(), suppressed_s)
     };
     stdout.set_color(ColorSpec::new().set_fg(None)).unwrap();
-    println!("{}","press Ctrl-C to exit");
-    println!("");
+    if exit_hint {
+        println!("press Ctrl-C to exit");
+    }
+    println!();
     for result in failed {
         println!("{}",result);
     };
@@ -64,115 +196,445 @@ fn main() {
     for result in errors {
         println!("{}",result);
     };
+    for result in skipped {
+        println!("{}",result);
+    };
+    for result in suppressed {
+        println!("{}",result);
+    };
     for result in passed {
         println!("{}",result);
     };
-    println!("");
-    loop {}
+    println!();
 }
 
-fn check(contents: &String, config_items: &HashMap<String,f32>) -> Vec<Outcome> {
-    let mut tool = Tool::Unknown;
-    let mut min = Point::empty();
-    let mut max = Point::empty();
-    let mut cut_min = Point::empty();
-    let mut traverse_min: f32 = f32::MAX;
-    let mut material_size = Point::empty();
-    let mut heights: HashMap<i32,usize> = HashMap::new();
-    let mut holes: HashSet<(i32,i32)> = HashSet::new();
+/// the name each check goes by in directives and the config, the name of its outcome,
+/// and the kinds of issue it finds, which can each be given their own severity
+const CHECKS: [(&str,&str,&[&str]); 17] = [
+    ("safe_height", "Min Safe Height", &["too_low", "low"]),
+    ("depth", "Depth", &["too_deep", "not_through"]),
+    ("offset", "Offset", &["not_centered", "far_from_origin", "negative"]),
+    ("passes", "Number of Passes", &["too_few", "too_many", "stepdown", "too_many_depths"]),
+    ("stepover", "Stepover", &["stepover", "slot_depth"]),
+    ("through_cut", "Through Cut", &["above_table", "long_tab"]),
+    ("cut_direction", "Cut Direction", &["wrong_direction"]),
+    ("tool_wear", "Tool Wear", &["past_life"]),
+    ("run_time", "Run Time", &["too_long"]),
+    ("dimensions", "Part Dimensions", &["past_soft_limit"]),
+    ("feed_rate", "Feed Rate", &["no_feed", "over_machine", "over_limit"]),
+    ("chip_load", "Chip Load", &["rubbing", "overloaded", "surface_speed"]),
+    ("spindle", "Spindle", &["stopped", "no_speed", "out_of_range", "not_stopped"]),
+    ("endmill_path", "Endmill Path", &["hole_collision"]),
+    ("order_of_operations", "Order of Operations", &["drill_after_endmill"]),
+    ("sequence", "Program Sequence", &["mode_not_set", "unsafe_rapid", "spindle_off", "no_end", "not_retracted", "no_spindle_stop", "stopped_early"]),
+    ("reference", "Reference", &["wrong_size", "wrong_position", "missing", "extra"]),
+];
 
-    let mut endmill_path = Outcome::new_full("Endmill Path", Status::Pass,
-        "endmill does not collide with any preexisting holes".into()
-    );
-    let mut order_of_op = Outcome::new_full("Order of Operations", Status::Pass,
-        "no drill path after endmill path".into()
-    );
+/// everything found out about one toolpath
+struct Report {
+    outcomes: Vec<Outcome>,
+    /// estimated, in seconds
+    run_time: Option<f32>,
+}
 
-    for line in contents.lines() {
-        if line.find("G0").is_some() || line.find("G1").is_some() { //moving
-            let point = Point::from_str(line);
-            min = min.min(point);
-            max = max.max(point);
-            if point.z.is_some() && material_size.z.is_some() { //has z coordinate
-                let height = point.z.unwrap();
-                let thickness = material_size.z.unwrap();
-                if height < thickness { //cutting
-                    cut_min = cut_min.min(point);
-                    if let Tool::Endmill(size) = tool {
-                        let height_int = (height * 1000.0) as i32;
-                        let count = heights.get_mut(&height_int);
-                        if let Some(t) = count {
-                            *t += 1
-                        } else {
-                            heights.insert(height_int,1);
-                        }
-                        if let Some(s) = size {
-                        if let Some(x) = point.x {
-                        if let Some(y) = point.y {
-                            for hole in &holes {
-                                let x_dist = x - ((hole.0 as f32)/1000.0);
-                                let y_dist = y - ((hole.1 as f32)/1000.0);
-                                if (x_dist * x_dist + y_dist * y_dist).sqrt() <= s {
-                                    endmill_path = endmill_path.set(Status::Fail,
-                                        format!("endmill may collide with drilled hole at ({},{})",x,y)
-                                    );
-                                }
-                            }
-                        }}}
-                    } else if let Tool::Drill(_) = tool {
-                        if let Some(x) = point.x {
-                        if let Some(y) = point.y {
-                            holes.insert(((x * 1000.0) as i32, (y * 1000.0) as i32));
-                        }}
-                    }
-                }
-                if height > thickness {
-                    traverse_min = (height-thickness).min(traverse_min);
+/// fills in the machine section of the config from a mach3 or linuxcnc profile, or the `--machine` profile's section.
+/// returns the exit code: 0 if the config was updated and 2 if it couldn't be
+fn import_machine(profile: &Path, args: &args::Args) -> i32 {
+    println!("---");
+    println!("Importing machine profile \'{}\'...",profile.display());
+    let (imported, notes) = match import::read(profile) {
+        Ok(read) => { read }
+        Err(error) => { eprintln!("Error: {}",error); return 2; }
+    };
+    let Some(file) = config::import_file(args) else {
+        eprintln!("Error: couldn't find the user config directory, pick a config file with --config");
+        return 2;
+    };
+    let section = args.machine.as_ref().map_or("machine".into(), |m| format!("machines.{}",m));
+    for value in &imported {
+        println!("{} = {} from {}",value.name.to_lowercase(),value.value,value.source);
+    }
+    for note in &notes {
+        println!("{}",note);
+    }
+    let values: Vec<(&str,f32)> = imported.iter().map(|v| (v.name.as_str(), v.value)).collect();
+    if let Err(error) = config::update(&file, &section, &values) {
+        eprintln!("Error: {}",error);
+        return 2;
+    }
+    println!("updated [{}] in \'{}\'",section,file.display());
+    0
+}
+
+/// validates several toolpaths at once, then prints each report and a summary table.
+/// returns the exit code: 0 if everything passed, 1 for warnings and 2 for any failure or error
+fn batch(paths: &[PathBuf], args: &args::Args) -> i32 {
+    //toolpaths in different directories can have different configs
+    let mut configs: HashMap<&Path,config::Config> = HashMap::new();
+    for dir in paths.iter().filter_map(|p| p.parent()) {
+        if !configs.contains_key(dir) {
+            let Some(config) = read_config(args, Some(dir)) else { return 2 };
+            configs.insert(dir, config);
+        }
+    }
+    let next = AtomicUsize::new(0);
+    let reports: Mutex<Vec<Option<Report>>> = Mutex::new(paths.iter().map(|_| None).collect());
+    let workers = std::thread::available_parallelism().map_or(4, |n| n.get()).min(paths.len());
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(i) else { break };
+                let report = check(path, &configs[path.parent().unwrap()], args).unwrap_or_else(|error| {
+                    Report { outcomes: vec![Outcome::new("File").set(Status::Error, error)], run_time: None }
+                });
+                reports.lock().unwrap()[i] = Some(report);
+            });
+        }
+    });
+    let reports: Vec<Report> = reports.into_inner().unwrap().into_iter().flatten().collect();
+
+    let mut table = vec![format!("{:<w$}  {:<11}{:>6}{:>6}{:>7}{:>6}  {}","file","status","fail","warn","error","skip","run time",
+        w = paths.iter().map(|p| p.display().to_string().len()).max().unwrap_or(0).max(4),
+    )];
+    let mut worst = Status::Pass;
+    for (path, report) in paths.iter().zip(&reports) {
+        println!("---");
+        println!("Validating file \'{}\'...",path.display());
+        print_results(&report.outcomes, false);
+
+        //skipped checks are counted in their own column, and don't stop a file passing
+        let status = report.outcomes.iter().map(|o| o.status.clone()).filter(|s| *s != Status::Skipped)
+            .max_by_key(|s| s.rank()).unwrap_or(Status::Pass);
+        let count = |status: Status| report.outcomes.iter().filter(|o| o.status == status).count();
+        table.push(format!("{:<w$}  {:<11}{:>6}{:>6}{:>7}{:>6}  {}",
+            path.display(), status.to_string(), count(Status::Fail), count(Status::Warning), count(Status::Error), count(Status::Skipped),
+            report.run_time.map_or("-".into(), estimate::format_time),
+            w = table[0].find("status").unwrap() - 2,
+        ));
+        if status.rank() > worst.rank() {
+            worst = status;
+        }
+    }
+    let files: Vec<(&Path, &[Outcome])> = paths.iter().zip(&reports).map(|(p, r)| (p.as_path(), r.outcomes.as_slice())).collect();
+    write_html(args, &files);
+    println!("===");
+    println!("{} files validated",reports.len());
+    for row in table {
+        println!("{}",row);
+    }
+    //the same missing input usually skips checks in many files, so each reason is only listed once
+    let mut reasons: Vec<(&Reason,Vec<&str>,usize)> = Vec::new();
+    for outcome in reports.iter().flat_map(|r| &r.outcomes) {
+        let Some(reason) = &outcome.reason else { continue };
+        match reasons.iter_mut().find(|(r,..)| *r == reason) {
+            Some((_, checks, count)) => {
+                if !checks.contains(&outcome.name.as_str()) {
+                    checks.push(&outcome.name);
                 }
+                *count += 1;
             }
+            None => reasons.push((reason, vec![&outcome.name], 1)),
+        }
+    }
+    for (reason, checks, count) in reasons {
+        println!("{} skipped {} times: {}",checks.join(", "),count,reason);
+    }
+    exit_code(&worst)
+}
+
+/// 0 if nothing was found wrong, 1 for warnings and 2 for failures and errors
+fn exit_code(worst: &Status) -> i32 {
+    match worst {
+        Status::Pass | Status::Suppressed | Status::Skipped => 0,
+        Status::Warning => 1,
+        _ => 2,
+    }
+}
+
+/// a toolpath resolved into moves, along with what's known about the stock it cuts
+struct Program {
+    state: gcode::State,
+    moves: Vec<gcode::Move>,
+    material: Option<String>,
+    material_size: Point,
+    thickness_source: String,
+    /// xy zero is the center of the stock, but the stock size isn't known
+    centered: bool,
+}
+
+/// the first pass over a program, for what changes how its moves are read: the stock in the header and the units it's in, and directives
+fn scan(path: &Path) -> Result<(Option<header::Stock>,directives::Directives),String> {
+    let mut header = String::new();
+    let mut in_header = true;
+    let mut units: Option<header::Units> = None;
+    let mut directives = directives::Directives::default();
+    read_lines(path, "scanning", |i, line| {
+        in_header = in_header && !header::ends_header(line);
+        if in_header {
+            header += line;
+            header.push('\n');
+        }
+        if units.is_none() {
+            units = header::program_units(line);
         }
-        else if line.find("(").is_some() && line.find(")").is_some() {
-            let point = Point::from_str(line);
-            if material_size.is_empty() && !point.is_empty() {
-                material_size = point;
+        directives.read_line(i, line);
+    })?;
+    Ok((header::parse(&header, units), directives))
+}
+
+/// the second pass over a program, resolving it into moves
+fn load(path: &Path, stock: Option<header::Stock>, config_items: &HashMap<String,f32>, args: &args::Args) -> Result<Program,String> {
+    let mut state = gcode::State::new();
+    //until the program picks its units with G20 or G21, they're taken to be the ones the stock was read in
+    state.metric = stock.as_ref().is_some_and(|s| s.units == Some(header::Units::Mm));
+    let mut moves: Vec<gcode::Move> = Vec::new();
+    let mut material = args.material.clone();
+    let materials = config::materials(config_items);
+    let mut material_size = stock.as_ref().map_or(Point::empty(), |s| s.size);
+    let mut thickness_source = stock.as_ref().map_or(String::new(), |s| format!("{} header",s.source));
+    let config_thickness = *config_items.get("MATERIAL_THICKNESS").unwrap();
+    if let Some(thickness) = args.thickness {
+        material_size.z = Some(thickness);
+        thickness_source = "--thickness".into();
+    } else if material_size.z.is_none() && config_thickness > 0.0 {
+        material_size.z = Some(config_thickness);
+        thickness_source = "config".into();
+    }
+
+    //moves are shifted so z zero is the table and xy zero is the southwest corner of the stock
+    let z_origin = args.z_origin.or(stock.as_ref().and_then(|s| s.z_origin)).unwrap_or(header::ZOrigin::Bottom);
+    let xy_origin = args.xy_origin.or(stock.as_ref().and_then(|s| s.xy_origin)).unwrap_or(header::XyOrigin::Corner);
+    if let (header::ZOrigin::Top, Some(thickness)) = (z_origin, material_size.z) {
+        state.offset[2] = thickness;
+        thickness_source += ", z zero on top of the stock";
+    }
+    let mut centered = false;
+    if xy_origin == header::XyOrigin::Center {
+        if let (Some(x), Some(y)) = (material_size.x, material_size.y) {
+            state.offset[0] = x / 2.0;
+            state.offset[1] = y / 2.0;
+        } else {
+            centered = true;
+        }
+    }
+
+    read_lines(path, "reading moves", |i, line| {
+        if let Some(mv) = state.apply(i, line) {
+            moves.push(mv);
+        } else if line.find("(").is_some() && line.find(")").is_some() {
+            if material.is_none() && line.to_lowercase().contains("material") {
+                let lower = line.to_lowercase();
+                material = materials.iter().find(|m| lower.contains(m.as_str())).cloned();
             }
             if line.find("Tool: Drill").is_some() {
-                if let Tool::Endmill(_) = tool {
-                    order_of_op = order_of_op.set(Status::Warning,
-                        format!("drilling after endmilling detected. Please check order of operations")
-                    );
-                }
-                tool = Tool::Drill(get_tool_size(line));
+                state.tool = Tool::Drill(get_tool_size(line));
+                state.flutes = get_flute_count(line);
             } else if line.find("Tool: End Mill").is_some() {
-                tool = Tool::Endmill(get_tool_size(line));
+                state.tool = Tool::Endmill(get_tool_size(line));
+                state.flutes = get_flute_count(line);
+            }
+        }
+    })?;
+    Ok(Program { state, moves, material, material_size, thickness_source, centered })
+}
+
+/// what changed between two revisions of a program
+fn compare(old_path: &Path, new_path: &Path, config: &config::Config, args: &args::Args) -> Result<Vec<Outcome>,String> {
+    let config_items = &config.items;
+    let old = load(old_path, scan(old_path)?.0, config_items, args)?;
+    let new = load(new_path, scan(new_path)?.0, config_items, args)?;
+    println!("Comparing file \'{}\' to \'{}\'...",new_path.display(),old_path.display());
+    let (mut outcomes, regions) = diff::diff(&old, &new, config_items);
+    outcomes.insert(0, config.outcome());
+    if let Some(svg) = &args.svg {
+        let plot = render::svg(&[
+            render::Layer { moves: &old.moves, color: "gray", label: format!("old: {}",old_path.display()) },
+            render::Layer { moves: &new.moves, color: "blue", label: format!("new: {}",new_path.display()) },
+        ], new.material_size.z, &regions);
+        if let Err(error) = render::write(svg, &plot) {
+            outcomes.push(Outcome::new("Plot").set(Status::Error, error));
+        }
+    }
+    Ok(outcomes)
+}
+
+fn check(path: &Path, config: &config::Config, args: &args::Args) -> Result<Report,String> {
+    let (stock, directives) = scan(path)?;
+    let (config, directive_errors) = directives.config(config);
+    let config_items = &config.items;
+    let Program { state, moves, material, material_size, thickness_source, centered } = load(path, stock, config_items, args)?;
+    if fs::metadata(path).is_ok_and(|m| m.len() > PROGRESS_SIZE) {
+        eprintln!("\'{}\': checking {} moves",path.display(),moves.len());
+    }
+    let mut min = Point::empty();
+    let mut cut_min = Point::empty();
+    let mut cut_max = Point::empty();
+    let mut traverse_min: f32 = f32::MAX;
+    for point in moves.iter().map(|m| m.to) {
+        min = min.min(point);
+        if let (Some(height), Some(thickness)) = (point.z, material_size.z) { //has z coordinate
+            if height < thickness { //cutting
+                cut_min = cut_min.min(point);
+                cut_max = cut_max.max(point);
+            }
+            if height > thickness {
+                traverse_min = (height-thickness).min(traverse_min);
             }
         }
     }
-   
-    vec![
-        check_safe_height(traverse_min,
+
+    //finding operations and estimating the run time both go over every move, and several checks need each of them
+    let (ops, estimate) = std::thread::scope(|scope| {
+        let estimate = scope.spawn(|| estimate::estimate(&moves, &state, &estimate::Machine::from_config(config_items)));
+        let ops = passes::operations(&moves, material_size.z,
+            *config_items.get("PASS_FREQUENCY_THRESHOLD").unwrap() as usize,
+        );
+        (ops, estimate.join().unwrap())
+    });
+    let material = material.as_deref();
+    let thickness = material_size.z;
+    //what each tool cut, kept after the checks so it can go in the tool ledger
+    let usages = OnceLock::new();
+    let mut checks: Vec<Check> = vec![
+        Box::new(|| config.outcome()),
+        Box::new(|| check_safe_height(traverse_min, thickness,
             *config_items.get("WARN_SAFE_HEIGHT").unwrap(),
             *config_items.get("FAIL_SAFE_HEIGHT").unwrap(),
-        ),
-        check_depth(min,material_size,
+        )),
+        Box::new(|| check_depth(min,material_size,&thickness_source,
             *config_items.get("DEPTH_THRESHOLD").unwrap(),
-        ),
-        check_offset(cut_min,
+        )),
+        Box::new(|| check_offset(cut_min,cut_max,thickness,centered,
             *config_items.get("MIN_OFFSET").unwrap(),
             *config_items.get("MAX_OFFSET").unwrap(),
-        ),
-        check_passes(heights,
-            *config_items.get("MIN_PASSES").unwrap() as usize,
-            *config_items.get("MAX_PASSES").unwrap() as usize,
-            *config_items.get("PASS_FREQUENCY_THRESHOLD").unwrap() as usize,
-        ),
-        check_dimensions(max,
-            *config_items.get("MACHINE_SIZE_X").unwrap(),
-            *config_items.get("MACHINE_SIZE_Y").unwrap(),
-        ),
-        endmill_path,
-        order_of_op,
-    ]
+        )),
+        Box::new(|| passes::check_passes(&ops, thickness, config_items, material)),
+        Box::new(|| passes::check_stepover(&ops, thickness, config_items, material)),
+        Box::new(|| profiles::check_through_cut(&ops, &moves, thickness,
+            *config_items.get("MAX_TAB_LENGTH").unwrap(),
+        )),
+        Box::new(|| profiles::check_direction(&moves, thickness, config_items, material)),
+        Box::new(|| wear::check_wear(usages.get_or_init(|| wear::usage(&ops, &moves, thickness)), config_items, material,
+            args.ledger.as_deref(), path,
+        )),
+        Box::new(|| estimate::check_time(&estimate,
+            *config_items.get("MAX_JOB_TIME").unwrap(),
+        )),
+        Box::new(|| check_soft_limits(&moves, config_items)),
+        Box::new(|| check_feeds(&moves, config_items, material)),
+        Box::new(|| check_chip_load(&moves, config_items, material, thickness)),
+        Box::new(|| check_spindle(&moves, &state, thickness,
+            *config_items.get("MIN_SPINDLE_RPM").unwrap(),
+            *config_items.get("MAX_SPINDLE_RPM").unwrap(),
+        )),
+        Box::new(|| check_endmill_path(&moves, thickness)),
+        Box::new(|| check_order_of_operations(&moves)),
+        Box::new(|| check_sequence(&moves, &state, thickness,
+            *config_items.get("FAIL_SAFE_HEIGHT").unwrap(),
+        )),
+    ];
+    if let Some(reference) = &args.reference {
+        checks.push(Box::new(|| reference::check_reference(reference::read(reference),
+            &reference::features(&ops, &moves, thickness),
+            *config_items.get("REFERENCE_TOLERANCE").unwrap(),
+        )));
+    }
+    let mut outcomes = run_checks(checks);
+    if let Some(svg) = &args.svg {
+        let plot = render::svg(&[render::Layer { moves: &moves, color: "black", label: path.display().to_string() }],
+            thickness, &[],
+        );
+        if let Err(error) = render::write(svg, &plot) {
+            outcomes.push(Outcome::new("Plot").set(Status::Error, error));
+        }
+    }
+    if let Some(errors) = directive_errors {
+        outcomes.insert(1, errors);
+    }
+    directives.suppress(&mut outcomes);
+    config.apply_severities(&mut outcomes);
+    //only count this program's cutting against the bits when tool wear is being checked
+    if let (Some(ledger), Some(usages)) = (&args.ledger, usages.get()) {
+        if !directives.allows_all("Tool Wear") && !config.is_off("tool_wear") {
+            if let Err(error) = wear::record(usages, config_items, material, ledger, path) {
+                outcomes.push(Outcome::new("Tool Ledger").set(Status::Error, error));
+            }
+        }
+    }
+    Ok(Report { outcomes, run_time: Some(estimate.total) })
+}
+
+/// a check that only reads the parsed program, so it can run alongside the others
+type Check<'a> = Box<dyn FnOnce() -> Outcome + Send + 'a>;
+
+/// runs checks on worker threads. the outcomes come back in the order the checks were given,
+/// however long each one takes
+fn run_checks(checks: Vec<Check>) -> Vec<Outcome> {
+    let checks: Vec<Mutex<Option<Check>>> = checks.into_iter().map(|c| Mutex::new(Some(c))).collect();
+    let next = AtomicUsize::new(0);
+    let outcomes: Mutex<Vec<Option<Outcome>>> = Mutex::new(checks.iter().map(|_| None).collect());
+    let workers = std::thread::available_parallelism().map_or(4, |n| n.get()).min(checks.len());
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(check) = checks.get(i) else { break };
+                let outcome = check.lock().unwrap().take().unwrap()();
+                outcomes.lock().unwrap()[i] = Some(outcome);
+            });
+        }
+    });
+    outcomes.into_inner().unwrap().into_iter().flatten().collect()
+}
+
+fn check_endmill_path(moves: &[gcode::Move], thickness: Option<f32>) -> Outcome {
+    let mut endmill_path = Outcome::new_full("Endmill Path", Status::Pass,
+        "endmill does not collide with any preexisting holes".into()
+    );
+    let mut holes: HashSet<(i32,i32)> = HashSet::new();
+    for mv in moves {
+        let point = mv.to;
+        if let (Some(height), Some(thickness)) = (point.z, thickness) {
+            if height < thickness { //cutting
+                if let Tool::Endmill(size) = mv.setup.tool {
+                    if let Some(s) = size {
+                    if let Some(x) = point.x {
+                    if let Some(y) = point.y {
+                        for hole in &holes {
+                            let x_dist = x - ((hole.0 as f32)/1000.0);
+                            let y_dist = y - ((hole.1 as f32)/1000.0);
+                            if (x_dist * x_dist + y_dist * y_dist).sqrt() <= s {
+                                endmill_path = Outcome::from_issues(&endmill_path.name, vec![Issue::new(Status::Fail, "hole_collision",
+                                    format!("endmill may collide with drilled hole at ({},{})",x,y)
+                                ).at(mv.line)], String::new());
+                            }
+                        }
+                    }}}
+                } else if let Tool::Drill(_) = mv.setup.tool {
+                    if let Some(x) = point.x {
+                    if let Some(y) = point.y {
+                        holes.insert(((x * 1000.0) as i32, (y * 1000.0) as i32));
+                    }}
+                }
+            }
+        }
+    }
+    endmill_path
+}
+
+fn check_order_of_operations(moves: &[gcode::Move]) -> Outcome {
+    let mut order_of_op = Outcome::new_full("Order of Operations", Status::Pass,
+        "no drill path after endmill path".into()
+    );
+    for (mv, previous) in moves.iter().zip(std::iter::once(None).chain(moves.iter().map(Some))) {
+        if let (Tool::Drill(_), Some(Tool::Endmill(_))) = (&mv.setup.tool, previous.map(|p| &p.setup.tool)) {
+            order_of_op = Outcome::from_issues(&order_of_op.name, vec![Issue::new(Status::Warning, "drill_after_endmill",
+                "drilling after endmilling detected. Please check order of operations".into()
+            ).at(mv.line)], String::new());
+        }
+    }
+    order_of_op
 }
 
 fn get_tool_size(line: &str) -> Option<f32> {
@@ -185,17 +647,21 @@ fn get_tool_size(line: &str) -> Option<f32> {
         number.parse::<f32>().unwrap()
     }) {
         Some(size)
-    }
-    else if let Some(size) = MILIS.find(line).map(|ma| {
-        let number = NUM_RE.find(ma.as_str()).unwrap().as_str();
-        number.parse::<f32>().unwrap()
-    }) {
-        Some(size * 0.0394)
     } else {
-        None
+        MILIS.find(line).map(|ma| {
+            let number = NUM_RE.find(ma.as_str()).unwrap().as_str();
+            number.parse::<f32>().unwrap() * 0.0394
+        })
     }
 }
 
+fn get_flute_count(line: &str) -> Option<u32> {
+    lazy_static! {
+        static ref FLUTES: Regex = Regex::new(r"(?i)([0-9]+) *-? *fl(ute)?").unwrap();
+    }
+    FLUTES.captures(line).and_then(|c| c[1].parse::<u32>().ok())
+}
+
 
 lazy_static! {
     static ref NUM_RE: Regex = Regex::new(r"-?[0-9]*\.?[0-9]+").unwrap();
@@ -207,130 +673,429 @@ enum Tool {
     Endmill(Option<f32>),
     Unknown,
 }
+impl Tool {
+    fn size(&self) -> Option<f32> {
+        match self {
+            Tool::Drill(size) | Tool::Endmill(size) => *size,
+            Tool::Unknown => None,
+        }
+    }
+}
+impl fmt::Display for Tool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (kind, size) = match self {
+            Tool::Drill(size) => ("drill", size),
+            Tool::Endmill(size) => ("end mill", size),
+            Tool::Unknown => return write!(f,"unknown tool"),
+        };
+        match size {
+            Some(s) => write!(f,"{}\" {}",s,kind),
+            None => write!(f,"{}",kind),
+        }
+    }
+}
 
 
-fn check_dimensions(max: Point, machine_size_x: f32, machine_size_y: f32) -> Outcome {
-    let out = Outcome::new("Part Dimensions");
-    let max_x = if max.x.is_some() { max.x.unwrap() } else { return out; };
-    let max_y = if max.y.is_some() { max.y.unwrap() } else { return out; };
-    if max_x > machine_size_x || max_y > machine_size_y {
-        return out.set(Status::Fail,
-            format!("part dimensions exceed machine dimensions:\npart dimensions: {}x{}, machine dimensions: {}x{}\nif machine dimensions are incorrect, they can be changed in the config",
-                max_x,
-                max_y,
-                machine_size_x,
-                machine_size_y,
-            )
-        );
+fn check_feeds(moves: &[gcode::Move], config_items: &HashMap<String,f32>, material: Option<&str>) -> Outcome {
+    let name = "Feed Rate";
+    let max_machine_feed = *config_items.get("MAX_MACHINE_FEED").unwrap();
+    let mut issues: Vec<Issue> = Vec::new();
+    let mut reported: HashSet<(bool,Option<u32>,i32)> = HashSet::new();
+    let mut max_cut: f32 = 0.0;
+    let mut max_plunge: f32 = 0.0;
+
+    let first_feed_move = moves.iter().find(|m| m.motion != gcode::Motion::Rapid);
+    if let Some(mv) = first_feed_move {
+        if mv.setup.feed.is_none() {
+            issues.push(Issue::new(Status::Fail, "no_feed",
+                format!("no feed rate set before the first cutting move, at line {}",mv.line)
+            ).at(mv.line));
+        }
     } else {
-        return out.set(Status::Pass,
-            format!("part dimensions are within machine dimensions:\npart dimensions: {}x{}, machine dimensions: {}x{}",
-                max_x,
-                max_y,
-                machine_size_x,
-                machine_size_y,
-            )
-        );
+        return Outcome::skipped(name, Reason::NoCuttingMoves);
     }
+
+    for mv in moves.iter().filter(|m| m.motion != gcode::Motion::Rapid) {
+        let feed = if let Some(f) = mv.setup.feed { f } else { continue; };
+        let plunge = mv.is_plunge();
+        let q = config::Qualifiers {
+            material,
+            tool_number: mv.setup.tool_number,
+            diameter: mv.setup.tool.size(),
+        };
+        let (kind, limit) = if plunge {
+            max_plunge = max_plunge.max(feed);
+            ("plunge", config::lookup(config_items, "MAX_PLUNGE_FEED", &q))
+        } else {
+            max_cut = max_cut.max(feed);
+            ("lateral", config::lookup(config_items, "MAX_CUT_FEED", &q))
+        };
+        //only report each feed once per tool
+        let key = (plunge, mv.setup.tool_number, (feed * 1000.0) as i32);
+        if (feed > max_machine_feed || feed > limit) && !reported.insert(key) {
+            continue;
+        }
+        if feed > max_machine_feed {
+            issues.push(Issue::new(Status::Warning, "over_machine",
+                format!("{} feed of {} at line {} is faster than the machine maximum of {}",kind,feed,mv.line,max_machine_feed)
+            ).at(mv.line));
+        } else if feed > limit {
+            issues.push(Issue::new(Status::Warning, "over_limit",
+                format!("{} feed of {} at line {} is faster than the limit of {} for {}{}",
+                    kind,feed,mv.line,limit,mv.tool_label(),
+                    material.map_or(String::new(), |m| format!(" in {}",m)),
+                )
+            ).at(mv.line));
+        }
+    }
+    Outcome::from_issues(name, issues,
+        format!("feed rates are within limits:\nmax lateral feed: {}, max plunge feed: {}",max_cut,max_plunge)
+    )
 }
 
-fn check_safe_height(traverse_min: f32, warn_safe_height: f32, fail_safe_height: f32) -> Outcome {
-    let out = Outcome::new("Min Safe Height");
-    if traverse_min <= fail_safe_height {
-        return out.set(Status::Fail,
-            format!("tool is in danger of colliding with screws:\nminimum traversing height detected: {}",traverse_min)
-        );
-    } else if traverse_min <= warn_safe_height {
-        return out.set(Status::Warning,
-            format!("tool may collide with screws:\nminimum traversing height detected: {}",traverse_min)
-        );
-    } else if traverse_min == f32::MAX {
-        return out.set(Status::Error,
-            format!("could not detect minimum traversing height")
-        );
-    } else {
-        return out.set(Status::Pass,
-            format!("tool is not in danger of colliding with screws:\nminimum traversing height detected: {}",traverse_min)
-        );
+fn check_chip_load(moves: &[gcode::Move], config_items: &HashMap<String,f32>, material: Option<&str>, thickness: Option<f32>) -> Outcome {
+    let name = "Chip Load";
+    let mut issues: Vec<Issue> = Vec::new();
+    let mut summary: Vec<String> = Vec::new();
+
+    //consecutive lateral cuts with the same tool, feed and spindle speed
+    let mut segments: Vec<Vec<&gcode::Move>> = Vec::new();
+    for mv in moves.iter().filter(|m| m.is_cutting(thickness) && !m.is_plunge()) {
+        if let Tool::Endmill(_) = mv.setup.tool {
+            match segments.last_mut() {
+                Some(seg) if seg[0].setup.tool_number == mv.setup.tool_number && seg[0].setup.tool == mv.setup.tool
+                    && seg[0].setup.feed == mv.setup.feed && seg[0].setup.rpm == mv.setup.rpm => seg.push(mv),
+                _ => segments.push(vec![mv]),
+            }
+        }
+    }
+
+    for seg in &segments {
+        let (first, last) = (seg[0], seg[seg.len() - 1]);
+        let (diameter, feed, rpm) = match (first.setup.tool.size(), first.setup.feed, first.setup.rpm) {
+            (Some(d), Some(f), Some(r)) if r > 0.0 => (d, f, r),
+            _ => continue,
+        };
+        let q = config::Qualifiers {
+            material,
+            tool_number: first.setup.tool_number,
+            diameter: Some(diameter),
+        };
+        let flutes = first.setup.flutes.unwrap_or(config::lookup(config_items, "FLUTES", &q) as u32).max(1);
+        let chip_load = feed / (rpm * flutes as f32);
+        let surface_speed = std::f32::consts::PI * diameter * rpm / 12.0;
+        let where_ = format!("{}, lines {}-{}",first.tool_label(),first.line,last.line);
+
+        let min_chip_load = config::lookup(config_items, "MIN_CHIP_LOAD", &q);
+        let max_chip_load = config::lookup(config_items, "MAX_CHIP_LOAD", &q);
+        if chip_load < min_chip_load {
+            issues.push(Issue::new(Status::Warning, "rubbing",
+                format!("tool may rub: chip load of {:.4} is below {} for {}",chip_load,min_chip_load,where_)
+            ).between((first.line, last.line)));
+        } else if chip_load > max_chip_load {
+            issues.push(Issue::new(Status::Warning, "overloaded",
+                format!("tool may be overloaded: chip load of {:.4} is above {} for {}",chip_load,max_chip_load,where_)
+            ).between((first.line, last.line)));
+        }
+        let min_surface_speed = config::lookup(config_items, "MIN_SURFACE_SPEED", &q);
+        let max_surface_speed = config::lookup(config_items, "MAX_SURFACE_SPEED", &q);
+        if surface_speed < min_surface_speed || surface_speed > max_surface_speed {
+            issues.push(Issue::new(Status::Warning, "surface_speed",
+                format!("surface speed of {:.0} is outside {}-{} for {}",surface_speed,min_surface_speed,max_surface_speed,where_)
+            ).between((first.line, last.line)));
+        }
+        summary.push(format!("{}: chip load {:.4}, surface speed {:.0}",where_,chip_load,surface_speed));
+    }
+
+    if segments.is_empty() {
+        return Outcome::skipped(name, Reason::NoCuttingMoves);
+    }
+    if summary.is_empty() {
+        let unknown = |missing: &dyn Fn(&gcode::Move) -> bool| segments.iter().all(|seg| missing(seg[0]));
+        let mut missing: Vec<&'static str> = Vec::new();
+        if unknown(&|m| m.setup.tool.size().is_none()) {
+            missing.push("tool size");
+        }
+        if unknown(&|m| m.setup.feed.is_none()) {
+            missing.push("feed rate");
+        }
+        if missing.is_empty() || unknown(&|m| m.setup.rpm.is_none_or(|r| r <= 0.0)) {
+            missing.push("spindle speed");
+        }
+        return Outcome::skipped(name, Reason::MissingToolInfo(missing));
     }
+    Outcome::from_issues(name, issues, summary.join("\n"))
 }
 
+fn check_spindle(moves: &[gcode::Move], state: &gcode::State, thickness: Option<f32>, min_rpm: f32, max_rpm: f32) -> Outcome {
+    let name = "Spindle";
+    let mut issues: Vec<Issue> = Vec::new();
+    let mut reported_rpms: Vec<f32> = Vec::new();
+    let mut stopped_cuts = 0;
+    let mut stopped_lines = (0, 0);
+    let mut unset_cuts = 0;
 
-fn check_passes(heights: HashMap<i32,usize>, min_passes: usize, max_passes: usize, pass_freq_threshold: usize) -> Outcome {
-    let out = Outcome::new("Number of Passes");
-    let mut passes = 0;
-    for (_,freq) in heights {
-        if freq > pass_freq_threshold {
-            passes += 1;
+    for mv in moves.iter().filter(|m| m.is_cutting(thickness)) {
+        if mv.setup.spindle == gcode::Spindle::Off {
+            if stopped_cuts == 0 {
+                stopped_lines.0 = mv.line;
+                issues.push(Issue::new(Status::Fail, "stopped",
+                    format!("cutting with the spindle stopped, first at line {} with {}",mv.line,mv.tool_label())
+                ).at(mv.line));
+            }
+            stopped_cuts += 1;
+            stopped_lines.1 = mv.line;
+        } else if mv.setup.rpm.is_none() {
+            if unset_cuts == 0 {
+                issues.push(Issue::new(Status::Fail, "no_speed",
+                    format!("cutting without a spindle speed (S) set, first at line {}",mv.line)
+                ).at(mv.line));
+            }
+            unset_cuts += 1;
+        } else if let Some(rpm) = mv.setup.rpm {
+            if (rpm < min_rpm || rpm > max_rpm) && !reported_rpms.contains(&rpm) {
+                reported_rpms.push(rpm);
+                issues.push(Issue::new(Status::Warning, "out_of_range",
+                    format!("spindle speed of {} at line {} is outside the machine range of {}-{} rpm",rpm,mv.line,min_rpm,max_rpm)
+                ).at(mv.line));
+            }
         }
     }
-    if (1..=min_passes).contains(&passes) {
-        return out.set(Status::Warning,
-            format!("only {} passes detected",passes)
-        );
-    } else if (min_passes..max_passes).contains(&passes) {
-        return out.set(Status::Pass,
-            format!("{} passes detected",passes)
-        );
-    } else if (max_passes..15).contains(&passes) {
-        return out.set(Status::Warning,
-            format!("{} passes detected, too many?",passes)
-        );
-    } else {
-        return out.set(Status::Error,
-            format!("could not detect number of passes")
-        );
+    if stopped_cuts > 1 {
+        issues.push(Issue::new(Status::Fail, "stopped", format!("{} cutting moves with the spindle stopped",stopped_cuts))
+            .between(stopped_lines));
+    }
+    for stop in &state.tool_changes {
+        if stop.spindle != gcode::Spindle::Off {
+            issues.push(Issue::new(Status::Warning, "not_stopped",
+                format!("spindle is not stopped before the tool change at line {}",stop.line)
+            ).at(stop.line));
+        }
+    }
+    if let Some(stop) = &state.end {
+        if stop.spindle != gcode::Spindle::Off {
+            issues.push(Issue::new(Status::Warning, "not_stopped",
+                format!("spindle is not stopped before the end of the program at line {}",stop.line)
+            ).at(stop.line));
+        }
+    }
+    Outcome::from_issues(name, issues,
+        "spindle is running for every cut, and stopped before tool changes and the end of the program".into()
+    )
+}
+
+/// the preamble sets up the machine and gets the tool clear before moving, and the postamble puts it away again
+fn check_sequence(moves: &[gcode::Move], state: &gcode::State, thickness: Option<f32>, fail_safe_height: f32) -> Outcome {
+    let name = "Program Sequence";
+    let mut issues: Vec<Issue> = Vec::new();
+    //without the stock thickness, any z above where the program cuts counts as safe
+    let safe = |z: Option<f32>| match (z, thickness) {
+        (Some(z), Some(t)) => z >= t + fail_safe_height,
+        (Some(z), None) => moves.iter().filter_map(|m| m.to.z).all(|c| z >= c),
+        (None, _) => false,
+    };
+
+    if let Some(first) = moves.first() {
+        for (set, what) in [(state.units_set, "units (G20 or G21)"), (state.distance_mode_set, "distance mode (G90 or G91)")] {
+            match set {
+                None => issues.push(Issue::new(Status::Fail, "mode_not_set", format!("{} never set, the machine will use whatever the last program left",what))),
+                Some(line) if line > first.line => issues.push(Issue::new(Status::Fail, "mode_not_set",
+                    format!("{} not set until line {}, after the first move at line {}",what,line,first.line)
+                ).between((first.line, line))),
+                _ => {}
+            }
+        }
+    }
+    let xy_rapid = moves.iter().find(|m| m.motion == gcode::Motion::Rapid && (m.from.x != m.to.x || m.from.y != m.to.y));
+    if let Some(rapid) = xy_rapid.filter(|r| !safe(r.from.z)) {
+        issues.push(Issue::new(Status::Fail, "unsafe_rapid", match rapid.from.z {
+            Some(z) => format!("first xy rapid at line {} starts at z {}, before retracting to a safe height",rapid.line,z),
+            None => format!("first xy rapid at line {} is before any z move, so the tool height is unknown",rapid.line),
+        }).at(rapid.line));
+    }
+    if let Some(cut) = moves.iter().find(|m| m.is_cutting(thickness)).filter(|m| m.setup.spindle == gcode::Spindle::Off) {
+        issues.push(Issue::new(Status::Fail, "spindle_off", format!("first cut at line {} is before the spindle is started",cut.line)).at(cut.line));
     }
 
+    match &state.end {
+        None => issues.push(Issue::new(Status::Fail, "no_end", format!("program has no end: no M30 or M2{}, the file may be incomplete",
+            moves.last().map_or(String::new(), |m| format!(" after the last move at line {}",m.line))
+        ))),
+        Some(end) => {
+            let last = moves.iter().rev().find(|m| m.line < end.line);
+            if let Some(last) = last.filter(|m| !safe(m.to.z)) {
+                issues.push(Issue::new(Status::Warning, "not_retracted",
+                    format!("tool isn't retracted to a safe height at the end of the program, the last move is at line {}",last.line)
+                ).at(last.line));
+            }
+            //moving to a parking spot after stopping the spindle is fine, as long as it's at a safe height
+            let retract = moves.iter().rev().filter(|m| m.line < end.line).find(|m| m.from.z != m.to.z);
+            match state.spindle_stop.filter(|l| *l <= end.line) {
+                None => issues.push(Issue::new(Status::Warning, "no_spindle_stop", format!("no M5 to stop the spindle before the end at line {}",end.line)).at(end.line)),
+                Some(stop) => if let Some(retract) = retract.filter(|m| m.line > stop) {
+                    issues.push(Issue::new(Status::Warning, "stopped_early",
+                        format!("spindle is stopped at line {} before the last z move at line {}",stop,retract.line)
+                    ).between((stop, retract.line)));
+                },
+            }
+        }
+    }
+    Outcome::from_issues(name, issues,
+        "units and distance mode are set before moving, the tool is retracted before rapids, and the program ends with M5 and M30".into()
+    )
+}
+
+/// every position on each axis, in machine coordinates, against the machine's soft limits
+fn check_soft_limits(moves: &[gcode::Move], config_items: &HashMap<String,f32>) -> Outcome {
+    let name = "Part Dimensions";
+    //the lowest and highest position on each axis, with the line it's first reached at
+    let mut low: [Option<(f32,usize)>;3] = [None;3];
+    let mut high: [Option<(f32,usize)>;3] = [None;3];
+    for mv in moves {
+        //arcs can bulge past their end points
+        let path = mv.xy_path();
+        for i in 0..3 {
+            let positions: Vec<f32> = if i == 2 || path.is_empty() {
+                mv.to[i].into_iter().collect()
+            } else {
+                path.iter().map(|p| if i == 0 { p.0 } else { p.1 }).collect()
+            };
+            for p in positions {
+                if low[i].is_none_or(|(l, _)| p < l) {
+                    low[i] = Some((p, mv.line));
+                }
+                if high[i].is_none_or(|(h, _)| p > h) {
+                    high[i] = Some((p, mv.line));
+                }
+            }
+        }
+    }
+    if low.iter().all(Option::is_none) {
+        return Outcome::skipped(name, Reason::NoPositions("x, y or z"));
+    }
+
+    let mut issues: Vec<Issue> = Vec::new();
+    let mut details: Vec<String> = Vec::new();
+    for (i, axis) in ["x", "y", "z"].iter().enumerate() {
+        let (Some((low, low_line)), Some((high, high_line))) = (low[i], high[i]) else { continue };
+        let get = |item: &str| *config_items.get(&format!("{}_{}",item,axis.to_uppercase())).unwrap();
+        let (origin, soft_min, soft_max) = (get("WORK_ORIGIN"), get("SOFT_MIN"), get("SOFT_MAX"));
+        let (low, high) = (low + origin, high + origin);
+        if low < soft_min {
+            issues.push(Issue::new(Status::Fail, "past_soft_limit",
+                format!("{} goes {:.3} past soft_min_{} at line {}: {:.3} in machine coordinates, the limit is {}",axis,soft_min - low,axis,low_line,low,soft_min)
+            ).at(low_line));
+        }
+        if high > soft_max {
+            issues.push(Issue::new(Status::Fail, "past_soft_limit",
+                format!("{} goes {:.3} past soft_max_{} at line {}: {:.3} in machine coordinates, the limit is {}",axis,high - soft_max,axis,high_line,high,soft_max)
+            ).at(high_line));
+        }
+        details.push(format!("{}: {:.3} to {:.3} in machine coordinates, soft limits {} to {}",axis,low,high,soft_min,soft_max));
+    }
+    Outcome::from_issues(name, issues, "every move is within the machine's soft limits".into())
+        .with_details(details.join("\n"))
+}
+
+fn check_safe_height(traverse_min: f32, thickness: Option<f32>, warn_safe_height: f32, fail_safe_height: f32) -> Outcome {
+    let out = Outcome::new("Min Safe Height");
+    if thickness.is_none() {
+        Outcome::skipped(&out.name, Reason::NoThickness)
+    } else if traverse_min <= fail_safe_height {
+        Outcome::from_issues(&out.name, vec![Issue::new(Status::Fail, "too_low", "tool is in danger of colliding with screws:".into())], String::new())
+            .with_details(format!("minimum traversing height detected: {}",traverse_min))
+    } else if traverse_min <= warn_safe_height {
+        Outcome::from_issues(&out.name, vec![Issue::new(Status::Warning, "low", "tool may collide with screws:".into())], String::new())
+            .with_details(format!("minimum traversing height detected: {}",traverse_min))
+    } else if traverse_min == f32::MAX {
+        Outcome::skipped(&out.name, Reason::NoTraverses)
+    } else {
+        out.set(Status::Pass,
+            format!("tool is not in danger of colliding with screws:\nminimum traversing height detected: {}",traverse_min)
+        )
+    }
 }
 
-fn check_depth(min: Point, material_size: Point, depth_threshold: f32) -> Outcome {
+
+fn check_depth(min: Point, material_size: Point, thickness_source: &str, depth_threshold: f32) -> Outcome {
     let out = Outcome::new("Depth");
-    if material_size.z.is_some() {
-        let thickness = material_size.z.unwrap();
-        if min.z.is_some() {
-            let max_depth = thickness - min.z.unwrap();
+    if let Some(thickness) = material_size.z {
+        if let Some(min_z) = min.z {
+            let max_depth = thickness - min_z;
+            let details = format!("material thickness: {}\nmax cut depth: {}",thickness,max_depth);
             if max_depth > thickness + depth_threshold {
-                return out.set(Status::Fail,
-                    format!("may cut too deep:\nmaterial thickness: {}\nmax cut depth: {}",thickness,max_depth)
-                );
+                return Outcome::from_issues(&out.name, vec![Issue::new(Status::Fail, "too_deep", "may cut too deep:".into())], String::new())
+                    .with_details(details);
             } else if max_depth < thickness {
-                return out.set(Status::Fail,
-                    format!("may not cut through material:\nmaterial thickness: {}\nmax cut depth: {}",thickness,max_depth)
-                );
+                return Outcome::from_issues(&out.name, vec![Issue::new(Status::Fail, "not_through", "may not cut through material:".into())], String::new())
+                    .with_details(details);
             } else {
                 return out.set(Status::Pass,
-                    format!("material thickness: {}, max cut depth: {}",thickness,max_depth)
+                    format!("material thickness: {} (from {}), max cut depth: {}",thickness,thickness_source,max_depth)
                 );
             }
         }
     }
-    return out.set(Status::Error,
-        "unable to check depth".into()
-    );
+    match material_size.z {
+        None => Outcome::skipped(&out.name, Reason::NoThickness),
+        Some(_) => Outcome::skipped(&out.name, Reason::NoPositions("z")),
+    }
 }
-fn check_offset(min: Point, min_offset: f32, max_offset: f32) -> Outcome {
+fn check_offset(min: Point, max: Point, thickness: Option<f32>, centered: bool, min_offset: f32, max_offset: f32) -> Outcome {
     let out = Outcome::new("Offset");
-    if min.x.is_some() && min.y.is_some() {
-        for i in 0..2 {
-            if min[i].unwrap() > max_offset {
-                return out.set(Status::Fail,
-                    format!("toolpath may be offset:\nsouthwest corner of part is far from the origin, at ({}, {})",min.x.unwrap(),min.y.unwrap())
-                );
+    if centered {
+        //xy zero is the center of the stock, but without the stock size the best we can do is check the part is centered
+        if let (Some(min_x), Some(min_y), Some(max_x), Some(max_y)) = (min.x, min.y, max.x, max.y) {
+            let center = ((min_x + max_x) / 2.0, (min_y + max_y) / 2.0);
+            if center.0.abs() > max_offset || center.1.abs() > max_offset {
+                return Outcome::from_issues(&out.name, vec![Issue::new(Status::Fail, "not_centered", "toolpath may be offset:".into())], String::new())
+                    .with_details(format!("part is not centered on the origin, its center is at ({}, {})",center.0,center.1));
             }
-            if min[i].unwrap() < min_offset {
-                return out.set(Status::Fail,
-                    format!("toolpath may be offset:\nsouthwest corner of part is negative, at ({}, {})",min.x.unwrap(),min.y.unwrap())
-                );
+            return out.set(Status::Pass,
+                format!("part is centered on the origin, at ({}, {})",center.0,center.1)
+            );
+        }
+    }
+    if let (Some(min_x), Some(min_y)) = (min.x, min.y) {
+        for v in [min_x, min_y] {
+            if v > max_offset {
+                return Outcome::from_issues(&out.name, vec![Issue::new(Status::Fail, "far_from_origin", "toolpath may be offset:".into())], String::new())
+                    .with_details(format!("southwest corner of part is far from the origin, at ({}, {})",min_x,min_y));
+            }
+            if v < min_offset {
+                return Outcome::from_issues(&out.name, vec![Issue::new(Status::Fail, "negative", "toolpath may be offset:".into())], String::new())
+                    .with_details(format!("southwest corner of part is negative, at ({}, {})",min_x,min_y));
             }
         }
         return out.set(Status::Pass,
-            format!("southeast corner of part is near the origin, at ({}, {})",min.x.unwrap(), min.y.unwrap())
+            format!("southeast corner of part is near the origin, at ({}, {})",min_x, min_y)
         );
     }
-    return out.set(Status::Error,
-        "unable to check offset".into()
-    );
+    match thickness {
+        None => Outcome::skipped(&out.name, Reason::NoThickness),
+        Some(_) => Outcome::skipped(&out.name, Reason::NoCuttingMoves),
+    }
+}
+
+/// something wrong that a check found. `kind` is the condition, from the check's list in `CHECKS`
+#[derive(Clone)]
+struct Issue {
+    status: Status,
+    kind: &'static str,
+    message: String,
+    /// the first and last line of the program it's about, if it's about particular lines
+    lines: Option<(usize,usize)>,
+}
+impl Issue {
+    fn new(status: Status, kind: &'static str, message: String) -> Issue {
+        Issue { status, kind, message, lines: None }
+    }
+    fn at(self, line: usize) -> Issue {
+        self.between((line, line))
+    }
+    fn between(self, lines: (usize,usize)) -> Issue {
+        Issue { lines: Some(lines), ..self }
+    }
 }
 
 #[derive(Clone)]
@@ -338,6 +1103,12 @@ struct Outcome {
     name: String,
     message: String,
     status: Status,
+    /// how many lines at the end of the message are details rather than issues
+    details: usize,
+    /// why it was skipped
+    reason: Option<Reason>,
+    /// each issue, when they were found separately
+    found: Vec<Issue>,
 }
 impl Outcome {
     fn new(name: &str) -> Outcome {
@@ -345,13 +1116,26 @@ impl Outcome {
             name: name.into(),
             message: "unknown error. make sure the input file is a valid toolpath".into(),
             status: Status::Error,
+            details: 0,
+            reason: None,
+            found: Vec::new(),
         }
     }
     fn new_full(name: &str, status: Status, message: String) -> Outcome {
         Outcome {
             name: name.into(),
-            status: status,
-            message: message,
+            status,
+            message,
+            details: 0,
+            reason: None,
+            found: Vec::new(),
+        }
+    }
+    /// couldn't be evaluated, saying exactly what was missing
+    fn skipped(name: &str, reason: Reason) -> Outcome {
+        Outcome {
+            reason: Some(reason.clone()),
+            ..Outcome::new_full(name, Status::Skipped, reason.to_string())
         }
     }
     fn set(mut self, status: Status, message: String) -> Outcome {
@@ -359,6 +1143,72 @@ impl Outcome {
         self.message = message;
         self
     }
+    /// passes with `pass_message` if there are no issues, otherwise takes the worst status and lists every issue
+    fn from_issues(name: &str, issues: Vec<Issue>, pass_message: String) -> Outcome {
+        let status = issues.iter().map(|i| i.status.clone()).max_by_key(|s| s.rank()).unwrap_or(Status::Pass);
+        let message = if issues.is_empty() {
+            pass_message
+        } else {
+            issues.iter().map(|i| i.message.as_str()).collect::<Vec<&str>>().join("\n")
+        };
+        Outcome { found: issues, ..Outcome::new_full(name, status, message) }
+    }
+    /// extra information that is shown whatever the status is
+    fn with_details(mut self, details: String) -> Outcome {
+        self.message = format!("{}\n{}",self.message,details);
+        self.details += details.lines().count().max(1);
+        self
+    }
+    /// the lines of the message that describe what's wrong, without the details
+    fn issues(&self) -> Vec<&str> {
+        let lines: Vec<&str> = self.message.lines().collect();
+        lines[..lines.len().saturating_sub(self.details)].to_vec()
+    }
+    /// the status of each issue, the outcome's own status for issues that weren't found separately
+    fn issue_statuses(&self) -> Vec<Status> {
+        let count = self.issues().len();
+        if self.found.len() == count { self.found.iter().map(|i| i.status.clone()).collect() } else { vec![self.status.clone(); count] }
+    }
+    /// the kind of each issue, if they were found separately
+    fn issue_kinds(&self) -> Vec<Option<&'static str>> {
+        let count = self.issues().len();
+        (0..count).map(|i| self.found.get(i).filter(|_| self.found.len() == count).map(|f| f.kind)).collect()
+    }
+    /// the lines each issue is about, for issues that were found separately and are about particular lines
+    fn issue_lines(&self) -> Vec<Option<(usize,usize)>> {
+        let count = self.issues().len();
+        (0..count).map(|i| self.found.get(i).filter(|_| self.found.len() == count).and_then(|f| f.lines)).collect()
+    }
+    /// changes how much what was found counts, saying why first
+    fn overrule(&mut self, status: Status, reason: String) {
+        self.status = status;
+        self.message = format!("{}\n{}",reason,self.message);
+    }
+    /// changes the status of some of the issues, each with the reason. if every issue changed for the same reason
+    /// the whole outcome is overruled, otherwise the reasons go after the issues they changed
+    fn change_issues(&mut self, changes: &[Option<(Status,String)>]) {
+        if changes.iter().all(Option::is_none) {
+            return;
+        }
+        let changed: Vec<Status> = self.issue_statuses().into_iter().zip(changes).map(|(s, c)| c.as_ref().map_or(s, |(c, _)| c.clone())).collect();
+        let status = changed.iter().filter(|s| **s != Status::Suppressed).max_by_key(|s| s.rank()).cloned().unwrap_or(Status::Suppressed);
+        let reason = |i: usize| changes[i].as_ref().map(|(_, r)| r.clone());
+        if let Some(reason) = reason(0).filter(|r| (0..changes.len()).all(|i| reason(i).as_ref() == Some(r))) {
+            let was = self.status.clone();
+            self.overrule(status, format!("{}, was {}",reason,was));
+        } else {
+            // only some of the issues changed, so say which. details come after the issues and are left alone
+            let lines: Vec<String> = self.message.lines().enumerate().map(|(i, line)| match changes.get(i) {
+                Some(Some((_, reason))) => format!("{} ({})",line,reason),
+                _ => line.to_string(),
+            }).collect();
+            self.message = lines.join("\n");
+            for (issue, status) in self.found.iter_mut().zip(changed) {
+                issue.status = status;
+            }
+            self.status = status;
+        }
+    }
 }
 impl fmt::Display for Outcome {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -369,27 +1219,36 @@ impl fmt::Display for Outcome {
             Status::Pass => {
                 let mut stdout = StandardStream::stdout(ColorChoice::Always);
                 stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green))).unwrap();
-                write!(f, "[{}] ", self.status);
+                write!(f, "[{}] ", self.status)?;
                 stdout.set_color(ColorSpec::new().set_fg(Some(Color::Cyan))).unwrap();
-                write!(f, "{}:\n", self.name);
+                writeln!(f, "{}:", self.name)?;
                 stdout.set_color(ColorSpec::new().set_fg(None)).unwrap();
                 write!(f, "{}", message)
             }
             Status::Warning => {
                 let mut stdout = StandardStream::stdout(ColorChoice::Always);
                 stdout.set_color(ColorSpec::new().set_fg(Some(Color::Yellow))).unwrap();
-                write!(f, "[{}] ", self.status);
+                write!(f, "[{}] ", self.status)?;
                 stdout.set_color(ColorSpec::new().set_fg(Some(Color::Cyan))).unwrap();
-                write!(f, "{}:\n", self.name);
+                writeln!(f, "{}:", self.name)?;
+                stdout.set_color(ColorSpec::new().set_fg(None)).unwrap();
+                write!(f, "{}", message)
+            }
+            Status::Suppressed | Status::Skipped => {
+                let mut stdout = StandardStream::stdout(ColorChoice::Always);
+                stdout.set_color(ColorSpec::new().set_fg(Some(Color::White))).unwrap();
+                write!(f, "[{}] ", self.status)?;
+                stdout.set_color(ColorSpec::new().set_fg(Some(Color::Cyan))).unwrap();
+                writeln!(f, "{}:", self.name)?;
                 stdout.set_color(ColorSpec::new().set_fg(None)).unwrap();
                 write!(f, "{}", message)
             }
             _=> {
                 let mut stdout = StandardStream::stdout(ColorChoice::Always);
                 stdout.set_color(ColorSpec::new().set_fg(Some(Color::Red))).unwrap();
-                write!(f, "[{}] ", self.status);
+                write!(f, "[{}] ", self.status)?;
                 stdout.set_color(ColorSpec::new().set_fg(Some(Color::Cyan))).unwrap();
-                write!(f, "{}:\n", self.name);
+                writeln!(f, "{}:", self.name)?;
                 stdout.set_color(ColorSpec::new().set_fg(None)).unwrap();
                 write!(f, "{}", message)
             }
@@ -398,30 +1257,108 @@ impl fmt::Display for Outcome {
 }
 
 
-#[derive(PartialEq, Clone)]
+#[derive(PartialEq, Clone, Debug)]
 enum Status {
     Pass,
     Fail,
     Warning,
     Error,
+    /// would have warned or failed, but a directive in the program allows it
+    Suppressed,
+    /// couldn't be checked, because something it needs isn't in the program
+    Skipped,
+}
+
+/// what a check was missing, when it couldn't be evaluated
+#[derive(PartialEq, Clone, Debug)]
+enum Reason {
+    NoThickness,
+    NoCuttingMoves,
+    /// no moves with positions on these axes
+    NoPositions(&'static str),
+    NoTraverses,
+    /// no end mill depth has more cutting moves than the pass frequency threshold
+    NoPasses(usize),
+    /// every end mill cuts at at least this many depths
+    TooManyDepths(usize),
+    /// what's unknown for every end mill cut: tool size, feed rate or spindle speed
+    MissingToolInfo(Vec<&'static str>),
+}
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::NoThickness => write!(f,"material thickness is unknown: add the stock to the program header, use --thickness, or set material_thickness in the config"),
+            Reason::NoCuttingMoves => write!(f,"no cutting moves found below the top of the stock"),
+            Reason::NoPositions(axes) => write!(f,"no moves with {} positions found",axes),
+            Reason::NoTraverses => write!(f,"no rapid moves above the stock found to measure the traversing height"),
+            Reason::NoPasses(threshold) => write!(f,"no end mill depth has more than {} cutting moves to count as a pass, lower pass_frequency_threshold in the config if the passes are short",threshold),
+            Reason::TooManyDepths(limit) => write!(f,"every end mill cuts at {} or more depths, too many to tell which are passes",limit),
+            Reason::MissingToolInfo(missing) => {
+                let fixes: Vec<&str> = missing.iter().map(|m| match *m {
+                    "tool size" => "add the size to the tool comment, e.g. (Tool: End Mill {0.25\" 2 flute})",
+                    "feed rate" => "set a feed with F before cutting",
+                    _ => "set a spindle speed with S before cutting",
+                }).collect();
+                write!(f,"{} unknown for every end mill cut: {}",missing.join(" and "),fixes.join(", "))
+            }
+        }
+    }
+}
+impl Status {
+    /// how bad a status is, for picking the worst of several
+    fn rank(&self) -> u8 {
+        match self {
+            Status::Pass | Status::Suppressed => 0,
+            Status::Skipped => 1,
+            Status::Warning => 2,
+            Status::Error => 3,
+            Status::Fail => 4,
+        }
+    }
 }
 impl fmt::Display for Status {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Status::Pass => write!(f,"{}","PASS"),
-            Status::Fail => write!(f,"{}","FAIL"),
-            Status::Warning => write!(f,"{}","WARNING"),
-            Status::Error => write!(f,"{}","ERROR"),
+            Status::Pass => write!(f,"PASS"),
+            Status::Fail => write!(f,"FAIL"),
+            Status::Warning => write!(f,"WARNING"),
+            Status::Error => write!(f,"ERROR"),
+            Status::Suppressed => write!(f,"SUPPRESSED"),
+            Status::Skipped => write!(f,"SKIPPED"),
         }
     }
 }
-fn get_file(path: PathBuf) -> Result<String,String> {
-    fs::read_to_string(path.clone()).map_err(|_| format!("couldn't read file: '{}'", path.display()))
+/// programs bigger than this, in bytes, report progress on stderr while they're read
+const PROGRESS_SIZE: u64 = 20_000_000;
+
+/// calls `f` with each line of a file and its line number. the file is read a buffer at a time rather than all at once,
+/// so the program's text is never held whole. the moves parsed from it are still all kept for the checks
+fn read_lines(path: &Path, pass: &str, mut f: impl FnMut(usize,&str)) -> Result<(),String> {
+    let error = || format!("couldn't read file: '{}'", path.display());
+    let file = File::open(path).map_err(|_| error())?;
+    let size = file.metadata().map_or(0, |m| m.len());
+    let mut reader = BufReader::new(file);
+    let mut line = String::new();
+    let (mut read, mut shown) = (0, 0);
+    for i in 1.. {
+        line.clear();
+        let n = reader.read_line(&mut line).map_err(|_| error())?;
+        if n == 0 {
+            break;
+        }
+        //line endings are stripped the same way as str::lines
+        f(i, line.strip_suffix('\n').map_or(line.as_str(), |l| l.strip_suffix('\r').unwrap_or(l)));
+        read += n as u64;
+        if size > PROGRESS_SIZE && read * 10 / size > shown {
+            shown = read * 10 / size;
+            eprintln!("\'{}\': {} {}%",path.display(),pass,shown * 10);
+        }
+    }
+    Ok(())
 }
-fn get_path() -> Result<PathBuf,String> {
-    if env::args().len() > 1 {
-        let path = env::args().nth(1).unwrap();
-        path.parse::<PathBuf>().map_err(|_| "no such path".into())
+fn get_path(path: Option<PathBuf>) -> Result<PathBuf,String> {
+    if let Some(path) = path {
+        Ok(path)
     } else {
         let open_path = if let Ok(contents) = fs::read_to_string(".last_path") {
             contents.parse::<PathBuf>().unwrap()
@@ -462,23 +1399,6 @@ impl Point {
     fn new(x: Option<f32>, y: Option<f32>, z: Option<f32>) -> Point {
         Point { x, y, z }
     }
-    fn from_str(input: &str) -> Point {
-        lazy_static! {
-            static ref RE: [Regex;3] = [
-                Regex::new(r"X[= ]*-?[0-9]+\.[0-9]*").unwrap(),
-                Regex::new(r"Y[= ]*-?[0-9]+\.[0-9]*").unwrap(),
-                Regex::new(r"Z[= ]*-?[0-9]+\.[0-9]*").unwrap(),
-            ];
-        }
-        let mut point = Point::empty();
-        for i in 0..3 {
-            point[i] = RE[i].find(input).map(|ma| {
-                let number = NUM_RE.find(ma.as_str()).unwrap().as_str();
-                number.parse::<f32>().unwrap()
-            });
-        }
-        point
-    }
     fn min(&self, other: Point) -> Point {
         let mut new = Point::empty();
         for i in 0..3 {
@@ -522,3 +1442,205 @@ impl ops::IndexMut<usize> for Point {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// crlf line endings, comments and blank lines, with no newline at the end,
+    /// and long enough to be read in many buffers
+    fn program() -> String {
+        let mut lines = vec!["G20 G90".to_string(), "S18000 M3".into(), "G0 Z0.5".into()];
+        for i in 0..2000 {
+            let (x, y) = ((i % 100) as f32 / 10.0, i as f32 / 200.0);
+            lines.push(format!("G1 X{:.4} Y{:.4} Z0.1 F{}",x,y,40 + i / 500 * 10));
+            if i % 7 == 0 {
+                lines.push(String::new());
+                lines.push("(a comment)".into());
+            }
+            if i % 11 == 0 {
+                lines.push(format!("G2 X{:.4} Y{:.4} I0.1 J0",x + 0.2,y));
+            }
+        }
+        lines.push("M5".into());
+        lines.push("M30".into());
+        lines.join("\r\n")
+    }
+
+    /// the moves in some lines of g-code cut with a 0.25 end mill, and the state after them
+    fn apply(lines: &[&str]) -> (Vec<gcode::Move>,gcode::State) {
+        let mut state = gcode::State::new();
+        state.tool = Tool::Endmill(Some(0.25));
+        let moves = lines.iter().enumerate().filter_map(|(i, line)| state.apply(i + 1, line)).collect();
+        (moves, state)
+    }
+
+    fn write(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("validate_toolpath_{}_{}",std::process::id(),name));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn g21_program_without_a_units_comment_has_its_stock_in_millimeters() {
+        let contents = ["(STOCK LOWER: X0 Y0 Z-6)", "(STOCK UPPER: X100 Y50 Z0)", "G21 G90", "S18000 M3",
+            "G0 X-5 Y-5 Z5", "G1 Z-6.0118 F300", "G1 X105", "G0 Z5", "M5", "M30"].join("\n");
+        let path = write("g21.txt", &contents);
+        let (stock, _) = scan(&path).unwrap();
+        let config_items = HashMap::from([("MATERIAL_THICKNESS".to_string(), 0.0)]);
+        let program = load(&path, stock, &config_items, &args::Args::default()).unwrap();
+        fs::remove_file(&path).unwrap();
+        let thickness = program.material_size.z.unwrap();
+        assert!((thickness - 6.0 / 25.4).abs() < 0.0001);
+        assert!((program.material_size.x.unwrap() - 100.0 / 25.4).abs() < 0.0001);
+        //z zero is on top of the stock, so the cut goes just through it
+        let deepest = program.moves.iter().filter_map(|m| m.to.z).fold(f32::MAX, f32::min);
+        assert!((deepest - (6.0 - 6.0118) / 25.4).abs() < 0.0001);
+    }
+
+    #[test]
+    fn read_lines_matches_reading_the_whole_file() {
+        let contents = program();
+        let path = write("lines.txt", &contents);
+        let mut streamed: Vec<(usize,String)> = Vec::new();
+        read_lines(&path, "reading", |i, line| streamed.push((i, line.to_string()))).unwrap();
+        fs::remove_file(&path).unwrap();
+        let whole: Vec<(usize,String)> = contents.lines().enumerate().map(|(i, line)| (i + 1, line.to_string())).collect();
+        assert_eq!(streamed, whole);
+    }
+
+    #[test]
+    fn load_matches_applying_the_whole_file() {
+        let contents = program();
+        let path = write("load.txt", &contents);
+        let config_items = HashMap::from([("MATERIAL_THICKNESS".to_string(), 0.0)]);
+        let program = load(&path, None, &config_items, &args::Args::default()).unwrap();
+        fs::remove_file(&path).unwrap();
+        let mut state = gcode::State::new();
+        let moves: Vec<gcode::Move> = contents.lines().enumerate().filter_map(|(i, line)| state.apply(i + 1, line)).collect();
+        assert_eq!(program.moves, moves);
+        //moves only get their own setup when something changes: the rapid before there's a feed, then each of the 4 feeds
+        let setups = program.moves.windows(2).filter(|w| !Arc::ptr_eq(&w[0].setup, &w[1].setup)).count() + 1;
+        assert_eq!(setups, 5);
+    }
+
+    #[test]
+    fn skipped_checks_exit_like_passing_ones() {
+        let worst = [Status::Pass, Status::Skipped, Status::Suppressed].into_iter().max_by_key(|s| s.rank()).unwrap();
+        assert_eq!(exit_code(&worst), 0);
+        assert_eq!(exit_code(&Status::Warning), 1);
+        assert_eq!(exit_code(&Status::Error), 2);
+    }
+
+    #[test]
+    fn feeds_over_a_tools_own_limit_are_reported_once() {
+        let config_items = HashMap::from([
+            ("MAX_MACHINE_FEED".to_string(), 300.0), ("MAX_CUT_FEED".to_string(), 100.0), ("MAX_CUT_FEED[T2]".to_string(), 50.0),
+            ("MAX_PLUNGE_FEED".to_string(), 30.0),
+        ]);
+        let (moves, _) = apply(&["G20 G90 T1", "G0 X0 Y0 Z1", "G1 Z0 F20", "G1 X1 F80", "T2", "G1 X2", "G1 X3", "G1 X4 F400"]);
+        let outcome = check_feeds(&moves, &config_items, None);
+        assert_eq!(outcome.status, Status::Warning);
+        assert_eq!(outcome.issue_kinds(), vec![Some("over_limit"), Some("over_machine")]);
+        assert_eq!(outcome.issue_lines(), vec![Some((6, 6)), Some((8, 8))]);
+    }
+
+    #[test]
+    fn cutting_with_the_spindle_stopped_fails_and_a_fast_spindle_warns() {
+        let (moves, state) = apply(&["G20 G90", "G0 X0 Y0 Z1", "G1 Z0 F20", "G1 X1", "S30000 M3", "G1 X2", "T2 M6", "M5", "M30"]);
+        let outcome = check_spindle(&moves, &state, Some(0.5), 5000.0, 24000.0);
+        assert_eq!(outcome.status, Status::Fail);
+        assert_eq!(outcome.issue_kinds(), vec![Some("stopped"), Some("out_of_range"), Some("stopped"), Some("not_stopped")]);
+        assert_eq!(outcome.issue_lines(), vec![Some((3, 3)), Some((6, 6)), Some((3, 4)), Some((7, 7))]);
+    }
+
+    #[test]
+    fn chip_load_is_worked_out_for_each_run_of_cuts() {
+        let config_items = HashMap::from([
+            ("FLUTES".to_string(), 2.0), ("MIN_CHIP_LOAD".to_string(), 0.001), ("MAX_CHIP_LOAD".to_string(), 0.002),
+            ("MIN_SURFACE_SPEED".to_string(), 100.0), ("MAX_SURFACE_SPEED".to_string(), 2000.0),
+        ]);
+        //0.0028 per tooth at 100 ipm, then 0.0011 at 40
+        let (moves, _) = apply(&["G20 G90", "S18000 M3", "G0 X0 Y0 Z1", "G1 Z0 F20", "G1 X1 F100", "G1 X2", "G1 X3 F40"]);
+        let outcome = check_chip_load(&moves, &config_items, None, Some(0.5));
+        assert_eq!(outcome.status, Status::Warning);
+        assert_eq!(outcome.issue_kinds(), vec![Some("overloaded")]);
+        assert_eq!(outcome.issue_lines(), vec![Some((5, 6))]);
+        assert!(outcome.message.contains("chip load of 0.0028 is above 0.002"));
+    }
+
+    #[test]
+    fn depth_is_measured_from_the_top_when_z_zero_is_on_the_stock() {
+        let depth = |cut: &str| {
+            let contents = ["( Material Size)", "( X= 12.000, Y= 12.000, Z= 0.750)", "( Z Origin for Material = Material Surface)",
+                "G20 G90", "S18000 M3", "G0 X0 Y0 Z0.5", &format!("G1 Z{} F20",cut), "G1 X1", "G0 Z0.5", "M5", "M30"].join("\n");
+            let path = write(&format!("depth{}.txt",cut), &contents);
+            let (stock, _) = scan(&path).unwrap();
+            let config_items = HashMap::from([("MATERIAL_THICKNESS".to_string(), 0.0)]);
+            let program = load(&path, stock, &config_items, &args::Args::default()).unwrap();
+            fs::remove_file(&path).unwrap();
+            let min = program.moves.iter().fold(Point::empty(), |min, m| min.min(m.to));
+            check_depth(min, program.material_size, &program.thickness_source, 0.05)
+        };
+        assert_eq!(depth("-0.76").status, Status::Pass);
+        assert_eq!(depth("-0.5").issue_kinds(), vec![Some("not_through")]);
+        assert_eq!(depth("-0.9").issue_kinds(), vec![Some("too_deep")]);
+    }
+
+    #[test]
+    fn checks_come_back_in_order_however_long_they_take() {
+        let checks: Vec<Check> = (0..8u64).map(|i| Box::new(move || {
+            std::thread::sleep(Duration::from_millis((8 - i) * 5));
+            Outcome::new(&format!("check {}",i))
+        }) as Check).collect();
+        let names: Vec<String> = run_checks(checks).into_iter().map(|o| o.name).collect();
+        assert_eq!(names, (0..8).map(|i| format!("check {}",i)).collect::<Vec<String>>());
+    }
+
+    #[test]
+    fn an_arc_bulging_past_a_soft_limit_fails() {
+        let mut config_items: HashMap<String,f32> = HashMap::new();
+        for axis in ["X", "Y", "Z"] {
+            config_items.insert(format!("WORK_ORIGIN_{}",axis), 0.0);
+            config_items.insert(format!("SOFT_MIN_{}",axis), -1.0);
+            config_items.insert(format!("SOFT_MAX_{}",axis), 10.0);
+        }
+        config_items.insert("SOFT_MAX_Y".into(), 0.5);
+        //both ends are at y 0, but the arc goes up to y 1 between them
+        let (moves, _) = apply(&["G20 G90", "G0 X0 Y0 Z1", "G1 Z0 F20", "G2 X2 Y0 I1 J0", "G0 Z1"]);
+        let outcome = check_soft_limits(&moves, &config_items);
+        assert_eq!(outcome.status, Status::Fail);
+        assert_eq!(outcome.issue_lines(), vec![Some((4, 4))]);
+        assert!(outcome.message.starts_with("y goes 0.500 past soft_max_y at line 4"), "{}", outcome.message);
+    }
+
+    #[test]
+    fn a_program_that_moves_before_setting_its_modes_and_never_ends_fails() {
+        let (moves, state) = apply(&["G20", "S18000 M3", "G0 Z1", "G0 X1 Y1", "G90", "G1 Z0 F20", "G1 X2"]);
+        let outcome = check_sequence(&moves, &state, Some(0.5), 0.1);
+        assert_eq!(outcome.status, Status::Fail);
+        assert_eq!(outcome.issue_kinds(), vec![Some("mode_not_set"), Some("no_end")]);
+        assert_eq!(outcome.issue_lines(), vec![Some((3, 5)), None]);
+
+        let (moves, state) = apply(&["G20 G90", "S18000 M3", "G0 Z1", "G0 X1 Y1", "G1 Z0 F20", "G1 X2", "G0 Z1", "M5", "M30"]);
+        assert_eq!(check_sequence(&moves, &state, Some(0.5), 0.1).status, Status::Pass);
+    }
+
+    #[test]
+    fn a_watched_file_is_ready_once_it_stops_changing() {
+        let listing = |files: &[(&str, u64)]| -> HashMap<PathBuf,Stamp> {
+            files.iter().map(|(name, size)| (PathBuf::from(name), (None, *size))).collect()
+        };
+        let mut pending: HashSet<PathBuf> = HashSet::new();
+        let start = listing(&[("a.nc", 10)]);
+        //b.nc is written over two listings, and a.nc is deleted while it's being written
+        let writing = listing(&[("a.nc", 20), ("b.nc", 5)]);
+        let written = listing(&[("b.nc", 50)]);
+        assert!(settled(&start, &writing, &mut pending).is_empty());
+        assert!(settled(&writing, &written, &mut pending).is_empty());
+        assert_eq!(pending, HashSet::from([PathBuf::from("b.nc")]));
+        assert_eq!(settled(&written, &written, &mut pending), vec![PathBuf::from("b.nc")]);
+        assert!(settled(&written, &written, &mut pending).is_empty());
+    }
+}
@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use crate::config;
+use crate::gcode::Move;
+use crate::{Issue, Outcome, Reason, Status, Tool};
+
+/// a run of end mill moves with one tool, split into the z levels it cuts at
+pub struct Operation<'a> {
+    pub moves: Vec<&'a Move>,
+    pub levels: Vec<Level<'a>>,
+}
+impl Operation<'_> {
+    pub fn label(&self) -> String {
+        self.moves[0].tool_label()
+    }
+    pub fn diameter(&self) -> Option<f32> {
+        self.moves[0].setup.tool.size()
+    }
+    /// the first and last line it cuts on
+    pub fn lines(&self) -> (usize,usize) {
+        (self.moves[0].line, self.moves[self.moves.len() - 1].line)
+    }
+}
+
+/// lateral cutting moves at one height, ordered from the top of the material down
+pub struct Level<'a> {
+    pub z: f32,
+    pub moves: Vec<&'a Move>,
+}
+
+pub fn operations(moves: &[Move], thickness: Option<f32>, pass_freq_threshold: usize) -> Vec<Operation<'_>> {
+    let mut runs: Vec<Vec<&Move>> = Vec::new();
+    for mv in moves {
+        match runs.last_mut() {
+            Some(run) if run[0].setup.tool == mv.setup.tool && run[0].setup.tool_number == mv.setup.tool_number => run.push(mv),
+            _ => runs.push(vec![mv]),
+        }
+    }
+    runs.into_iter().filter(|run| matches!(run[0].setup.tool, Tool::Endmill(_))).map(|run| {
+        let mut heights: HashMap<i32,Vec<&Move>> = HashMap::new();
+        for mv in run.iter().filter(|m| m.is_cutting(thickness) && m.from.z == m.to.z) {
+            if let Some(z) = mv.to.z {
+                heights.entry((z * 1000.0).round() as i32).or_default().push(mv);
+            }
+        }
+        let mut levels: Vec<Level> = heights.into_iter()
+            .filter(|(_,level_moves)| level_moves.len() > pass_freq_threshold)
+            .map(|(z,level_moves)| Level { z: z as f32 / 1000.0, moves: level_moves })
+            .collect();
+        levels.sort_by(|a,b| b.z.total_cmp(&a.z));
+        Operation { moves: run, levels }
+    }).collect()
+}
+
+/// every height an operation cuts sideways at, however few moves it has, from the top down.
+/// moves that ramp down count at their lowest point
+pub fn cut_heights<'a>(moves: &[&'a Move], thickness: Option<f32>) -> Vec<Level<'a>> {
+    let mut heights: HashMap<i32,Vec<&Move>> = HashMap::new();
+    for mv in moves.iter().filter(|m| m.is_cutting(thickness) && (m.from.x != m.to.x || m.from.y != m.to.y)) {
+        if let Some(z) = mv.from.z.into_iter().chain(mv.to.z).reduce(f32::min) {
+            heights.entry((z * 1000.0).round() as i32).or_default().push(mv);
+        }
+    }
+    let mut levels: Vec<Level> = heights.into_iter()
+        .map(|(z,level_moves)| Level { z: z as f32 / 1000.0, moves: level_moves })
+        .collect();
+    levels.sort_by(|a,b| b.z.total_cmp(&a.z));
+    levels
+}
+
+/// a tool cutting at this many depths is probably surfacing or ramping, not stepping down in passes
+const TOO_MANY_DEPTHS: usize = 15;
+
+pub fn check_passes(ops: &[Operation], thickness: Option<f32>, config_items: &HashMap<String,f32>, material: Option<&str>) -> Outcome {
+    let name = "Number of Passes";
+    let min_passes = *config_items.get("MIN_PASSES").unwrap() as usize;
+    let max_passes = *config_items.get("MAX_PASSES").unwrap() as usize;
+    let mut issues: Vec<Issue> = Vec::new();
+    let mut table = vec![format!("{:<24}{:>6}{:>10}{:>10}","tool","pass","z","stepdown")];
+    let mut too_deep: Vec<Issue> = Vec::new();
+
+    for op in ops.iter().filter(|op| !op.levels.is_empty()) {
+        let passes = op.levels.len();
+        if passes >= TOO_MANY_DEPTHS {
+            too_deep.push(Issue::new(Status::Warning, "too_many_depths",
+                format!("{} cutting depths found for {}, too many to tell which are passes",passes,op.label())
+            ).between(op.lines()));
+            continue;
+        }
+        if (1..=min_passes).contains(&passes) {
+            issues.push(Issue::new(Status::Warning, "too_few", format!("only {} passes detected for {}",passes,op.label())).between(op.lines()));
+        } else if passes >= max_passes {
+            issues.push(Issue::new(Status::Warning, "too_many", format!("{} passes detected for {}, too many?",passes,op.label())).between(op.lines()));
+        }
+
+        let q = config::Qualifiers {
+            material,
+            tool_number: op.moves[0].setup.tool_number,
+            diameter: op.diameter(),
+        };
+        let max_stepdown = op.diameter().map(|d| d * config::lookup(config_items, "MAX_STEPDOWN", &q));
+        //levels too short to count as passes still take material off, so the stepdown is from the nearest height cut above
+        let heights: Vec<f32> = cut_heights(&op.moves, thickness).iter().map(|l| l.z).collect();
+        for (i, level) in op.levels.iter().enumerate() {
+            let above = heights.iter().copied().filter(|z| *z > level.z + 0.0005).reduce(f32::min).or(thickness);
+            let stepdown = above.map(|a| a - level.z);
+            if let (Some(step), Some(max)) = (stepdown, max_stepdown) {
+                if step > max + 0.0005 {
+                    issues.push(Issue::new(Status::Warning, "stepdown",
+                        format!("stepdown of {:.3} down to z {} at line {} is more than the limit of {:.3} for {}",
+                            step,level.z,level.moves[0].line,max,op.label())
+                    ).at(level.moves[0].line));
+                }
+            }
+            table.push(format!("{:<24}{:>6}{:>10.3}{:>10}{}",
+                if i == 0 { op.label() } else { String::new() },
+                i + 1,
+                level.z,
+                stepdown.map_or("-".into(), |s| format!("{:.3}",s)),
+                if i + 1 == passes { " (final)" } else { "" },
+            ));
+        }
+    }
+
+    if table.len() == 1 && !too_deep.is_empty() {
+        return Outcome::skipped(name, Reason::TooManyDepths(TOO_MANY_DEPTHS));
+    }
+    //the other tools' passes can still be checked, so just warn about these
+    issues.extend(too_deep);
+    if table.len() == 1 {
+        let cutting = ops.iter().any(|op| op.moves.iter().any(|m| m.is_cutting(thickness)));
+        return Outcome::skipped(name, match thickness {
+            None => Reason::NoThickness,
+            Some(_) if !cutting => Reason::NoCuttingMoves,
+            Some(_) => Reason::NoPasses(*config_items.get("PASS_FREQUENCY_THRESHOLD").unwrap() as usize),
+        });
+    }
+    let passes: usize = ops.iter().map(|op| op.levels.len()).sum();
+    Outcome::from_issues(name, issues,
+        format!("{} passes detected",passes)
+    ).with_details(table.join("\n"))
+}
+
+type Segment = ((f32,f32),(f32,f32));
+
+/// the xy line a lateral move follows, if both ends are known
+fn segment(mv: &Move) -> Option<Segment> {
+    match (mv.from.x, mv.from.y, mv.to.x, mv.to.y) {
+        (Some(x0), Some(y0), Some(x1), Some(y1)) if (x1-x0).hypot(y1-y0) > 0.0001 => Some(((x0,y0),(x1,y1))),
+        _ => None,
+    }
+}
+
+/// the grid cells a segment passes through, found by stepping along it a quarter of a cell at a time
+fn cells_along(a: (f32,f32), b: (f32,f32), cell: f32) -> Vec<(i32,i32)> {
+    let steps = ((b.0-a.0).hypot(b.1-a.1) / cell * 4.0).ceil().max(1.0) as usize;
+    let mut cells: Vec<(i32,i32)> = (0..=steps).map(|k| {
+        let t = k as f32 / steps as f32;
+        (((a.0 + (b.0-a.0) * t) / cell).floor() as i32, ((a.1 + (b.1-a.1) * t) / cell).floor() as i32)
+    }).collect();
+    cells.dedup();
+    cells
+}
+
+/// distance from each lateral move to the nearest parallel move beside it at the same height,
+/// or `None` if nothing is cut within `reach`
+pub fn neighbour_distances<'a>(level: &Level<'a>, reach: f32) -> Vec<(&'a Move,Option<f32>)> {
+    let segments: Vec<(&'a Move,Segment)> = level.moves.iter()
+        .filter(|m| m.motion == crate::gcode::Motion::Linear)
+        .filter_map(|m| segment(m).map(|s| (*m,s)))
+        .collect();
+
+    //bucket segments into a grid so only nearby ones are compared. a segment goes in the cells it passes through,
+    //not every cell in its bounding box, which for a long diagonal move would be most of the grid
+    let cell = reach.max(0.001);
+    let mut grid: HashMap<(i32,i32),Vec<usize>> = HashMap::new();
+    for (i, (_,(a,b))) in segments.iter().enumerate() {
+        for c in cells_along(*a, *b, cell) {
+            grid.entry(c).or_default().push(i);
+        }
+    }
+
+    let lengths: Vec<f32> = segments.iter().map(|(_,(a,b))| (b.0-a.0).hypot(b.1-a.1)).collect();
+    segments.iter().enumerate().map(|(i, (mv,(a,b)))| {
+        let len = lengths[i];
+        let dir = ((b.0-a.0)/len, (b.1-a.1)/len);
+        let mut nearest: Option<f32> = None;
+        //the cells around the ones this passes through. a segment in several of them is just measured again,
+        //which is quicker than remembering it
+        let mut around: Vec<(i32,i32)> = cells_along(*a, *b, cell).into_iter()
+            .flat_map(|(cx,cy)| (-1..=1).flat_map(move |dx| (-1..=1).map(move |dy| (cx+dx,cy+dy))))
+            .collect();
+        around.sort_unstable();
+        around.dedup();
+        for c in around {
+            {
+                for &j in grid.get(&c).into_iter().flatten() {
+                    if j == i {
+                        continue;
+                    }
+                    let (c,d) = segments[j].1;
+                    let cross = (dir.0 * (d.1-c.1) - dir.1 * (d.0-c.0)) / lengths[j];
+                    if cross.abs() > 0.05 {
+                        continue; //not parallel
+                    }
+                    //the other move has to run alongside this one, not just in line with it
+                    let along_c = (c.0-a.0) * dir.0 + (c.1-a.1) * dir.1;
+                    let along_d = (d.0-a.0) * dir.0 + (d.1-a.1) * dir.1;
+                    if along_c.max(along_d) <= 0.0 || along_c.min(along_d) >= len {
+                        continue;
+                    }
+                    let mid = ((c.0+d.0)/2.0 - a.0, (c.1+d.1)/2.0 - a.1);
+                    let dist = (dir.0 * mid.1 - dir.1 * mid.0).abs();
+                    if dist > 0.001 && dist <= reach && nearest.is_none_or(|n| dist < n) {
+                        nearest = Some(dist);
+                    }
+                }
+            }
+        }
+        (*mv,nearest)
+    }).collect()
+}
+
+pub fn check_stepover(ops: &[Operation], thickness: Option<f32>, config_items: &HashMap<String,f32>, material: Option<&str>) -> Outcome {
+    let name = "Stepover";
+    let mut issues: Vec<Issue> = Vec::new();
+    let mut summary: Vec<String> = Vec::new();
+
+    for op in ops {
+        let diameter = if let Some(d) = op.diameter() { d } else { continue; };
+        let q = config::Qualifiers {
+            material,
+            tool_number: op.moves[0].setup.tool_number,
+            diameter: Some(diameter),
+        };
+        let max_stepover = diameter * config::lookup(config_items, "MAX_STEPOVER", &q);
+        let max_slot_depth = diameter * config::lookup(config_items, "MAX_SLOT_DEPTH", &q);
+        let mut above = thickness;
+        for level in &op.levels {
+            let depth = above.map(|a| a - level.z);
+            above = Some(level.z);
+            let distances = neighbour_distances(level, diameter * 2.0);
+            //passes with another pass less than a tool width away are clearing a pocket
+            let pocket: Vec<(&Move,f32)> = distances.iter()
+                .filter_map(|(m,d)| d.filter(|d| *d < diameter).map(|d| (*m,d)))
+                .collect();
+            if pocket.is_empty() {
+                continue;
+            }
+            let (widest_move, widest) = pocket.iter().fold(pocket[0], |w, p| if p.1 > w.1 { *p } else { w });
+            summary.push(format!("{} at z {}: stepover {:.3} ({:.0}% of diameter)",
+                op.label(),level.z,widest,widest / diameter * 100.0
+            ));
+            if widest > max_stepover + 0.0005 {
+                issues.push(Issue::new(Status::Warning, "stepover",
+                    format!("stepover of {:.3} at line {} is more than the limit of {:.3} for {}",
+                        widest,widest_move.line,max_stepover,op.label())
+                ).at(widest_move.line));
+            }
+            //short moves with nothing beside them, like the step between zigzag passes, aren't slots
+            let slot = distances.iter().find(|(m,d)| d.is_none_or(|d| d >= diameter) && m.length() > diameter);
+            if let (Some((slot_move,_)), Some(depth)) = (slot, depth) {
+                if depth > max_slot_depth + 0.0005 {
+                    issues.push(Issue::new(Status::Warning, "slot_depth",
+                        format!("full width slot {:.3} deep at line {} is deeper than the limit of {:.3} for {}",
+                            depth,slot_move.line,max_slot_depth,op.label())
+                    ).at(slot_move.line));
+                }
+            }
+        }
+    }
+
+    if summary.is_empty() {
+        return Outcome::new_full(name, Status::Pass, "no pocketing detected".into());
+    }
+    Outcome::from_issues(name, issues, "stepover is within limits".into())
+        .with_details(summary.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gcode::State;
+
+    /// moves cut with a 0.25 end mill from g-code lines, after a plunge to z0
+    fn program(cuts: &[&str]) -> Vec<Move> {
+        let mut lines = vec!["G20 G90", "S18000 M3", "G0 Z1", "G0 X0 Y0", "G1 Z0 F20"];
+        lines.extend(cuts);
+        let mut state = State::new();
+        state.tool = Tool::Endmill(Some(0.25));
+        lines.iter().enumerate().filter_map(|(i, line)| state.apply(i + 1, line)).collect()
+    }
+
+    #[test]
+    fn long_diagonal_moves_find_the_move_beside_them() {
+        //two long diagonals 0.2 apart, and a crossing diagonal that isn't parallel to either
+        let moves = program(&["G1 X40 Y40 F100", "G1 X40.1414 Y39.8586", "G1 X0.1414 Y-0.1414", "G1 X40 Y-0.1414", "G1 X0.1414 Y39.8586"]);
+        let refs: Vec<&Move> = moves.iter().collect();
+        let levels = cut_heights(&refs, Some(0.5));
+        let distances = neighbour_distances(&levels[0], 0.25);
+        let near: Vec<Option<f32>> = distances.iter().map(|(_,d)| d.map(|d| (d * 100.0).round() / 100.0)).collect();
+        assert_eq!(near, vec![Some(0.2), None, Some(0.2), None, None]);
+    }
+
+    #[test]
+    fn stepdown_is_from_the_level_above_even_if_it_is_too_short_to_be_a_pass() {
+        let mut cuts: Vec<String> = Vec::new();
+        for (z, count) in [(0.4, 25), (0.3, 2), (0.2, 25)] {
+            cuts.push(format!("G1 Z{}",z));
+            cuts.extend((0..count).map(|i| format!("G1 X{}",(i + 1) % 2)));
+        }
+        let moves = program(&cuts.iter().map(|c| c.as_str()).collect::<Vec<&str>>());
+        let ops = operations(&moves, Some(0.5), 20);
+        assert_eq!(ops[0].levels.len(), 2);
+        let config_items = HashMap::from([
+            ("MIN_PASSES".to_string(), 1.0), ("MAX_PASSES".to_string(), 6.0), ("MAX_STEPDOWN".to_string(), 0.5),
+        ]);
+        let outcome = check_passes(&ops, Some(0.5), &config_items, None);
+        assert_eq!(outcome.status, Status::Pass);
+        assert!(outcome.message.ends_with("0.200     0.100 (final)"));
+    }
+
+    #[test]
+    fn pocket_passes_wider_apart_than_the_limit_warn() {
+        let config_items = HashMap::from([("MAX_STEPOVER".to_string(), 0.5), ("MAX_SLOT_DEPTH".to_string(), 1.0)]);
+        let stepover = |step: f32| {
+            let mut cuts = vec!["G1 Z0.3".to_string()];
+            for row in 0..25 {
+                cuts.push(format!("G1 X{}",if row % 2 == 0 { 4 } else { 0 }));
+                cuts.push(format!("G1 Y{}",(row + 1) as f32 * step));
+            }
+            let moves = program(&cuts.iter().map(|c| c.as_str()).collect::<Vec<&str>>());
+            check_stepover(&operations(&moves, Some(0.5), 20), Some(0.5), &config_items, None)
+        };
+        let wide = stepover(0.2);
+        assert_eq!(wide.status, Status::Warning);
+        assert_eq!(wide.issue_kinds(), vec![Some("stepover")]);
+        assert_eq!(stepover(0.1).status, Status::Pass);
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+use crate::config;
+use crate::gcode::{Move, Spindle};
+use crate::passes::{self, Operation};
+use crate::{Issue, Outcome, Reason, Status};
+
+/// an unbroken chain of cutting moves with one tool. a chain that ends where it started is a closed profile
+pub struct Profile<'a> {
+    pub moves: Vec<&'a Move>,
+    pub points: Vec<(f32,f32)>,
+    pub closed: bool,
+}
+impl Profile<'_> {
+    pub fn label(&self) -> String {
+        self.moves[0].tool_label()
+    }
+    pub fn lines(&self) -> (usize,usize) {
+        (self.moves[0].line, self.moves[self.moves.len() - 1].line)
+    }
+    /// lowest z the profile cuts at, ignoring moves straight up and down
+    pub fn depth(&self) -> Option<f32> {
+        self.moves.iter().filter(|m| m.from.x != m.to.x || m.from.y != m.to.y)
+            .filter_map(|m| m.to.z)
+            .reduce(f32::min)
+    }
+    /// shoelace area, positive if the profile goes counterclockwise
+    pub fn area(&self) -> f32 {
+        let n = self.points.len();
+        (0..n).map(|i| {
+            let (a, b) = (self.points[i], self.points[(i + 1) % n]);
+            a.0 * b.1 - b.0 * a.1
+        }).sum::<f32>() / 2.0
+    }
+    pub fn bounds(&self) -> ((f32,f32),(f32,f32)) {
+        self.points.iter().fold(((f32::MAX,f32::MAX),(f32::MIN,f32::MIN)), |(min,max),p| {
+            ((min.0.min(p.0), min.1.min(p.1)), (max.0.max(p.0), max.1.max(p.1)))
+        })
+    }
+    pub fn contains(&self, p: (f32,f32)) -> bool {
+        let n = self.points.len();
+        let mut inside = false;
+        for i in 0..n {
+            let (a, b) = (self.points[i], self.points[(i + n - 1) % n]);
+            if (a.1 > p.1) != (b.1 > p.1) && p.0 < (b.0 - a.0) * (p.1 - a.1) / (b.1 - a.1) + a.0 {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+    /// the same outline cut again on another pass
+    fn same_outline(&self, other: &Profile) -> bool {
+        let (a, b) = (self.bounds(), other.bounds());
+        (a.0.0 - b.0.0).abs() < 0.001 && (a.0.1 - b.0.1).abs() < 0.001 &&
+            (a.1.0 - b.1.0).abs() < 0.001 && (a.1.1 - b.1.1).abs() < 0.001
+    }
+}
+
+/// splits the cutting moves into profiles. moves straight up and down inside a chain (like tabs) stay part of it
+pub fn profiles(moves: &[Move], thickness: Option<f32>) -> Vec<Profile<'_>> {
+    let mut profiles: Vec<Profile> = Vec::new();
+    let mut chain: Vec<&Move> = Vec::new();
+    let mut points: Vec<(f32,f32)> = Vec::new();
+    fn finish<'a>(profiles: &mut Vec<Profile<'a>>, chain: &mut Vec<&'a Move>, points: &mut Vec<(f32,f32)>, closed: bool) {
+        if chain.iter().any(|m| m.from.x != m.to.x || m.from.y != m.to.y) {
+            profiles.push(Profile { moves: std::mem::take(chain), points: std::mem::take(points), closed });
+        }
+        chain.clear();
+        points.clear();
+    }
+    for mv in moves {
+        let same_tool = chain.first().is_none_or(|c| c.setup.tool == mv.setup.tool && c.setup.tool_number == mv.setup.tool_number);
+        if !mv.is_cutting(thickness) || !same_tool {
+            finish(&mut profiles, &mut chain, &mut points, false);
+            if !mv.is_cutting(thickness) {
+                continue;
+            }
+        }
+        let lateral = mv.from.x != mv.to.x || mv.from.y != mv.to.y;
+        if chain.is_empty() {
+            if !lateral {
+                continue; //plunging to the start of the profile
+            }
+            match (mv.from.x, mv.from.y) {
+                (Some(x), Some(y)) => points.push((x, y)),
+                _ => continue,
+            }
+        }
+        chain.push(mv);
+        if lateral {
+            points.extend(mv.xy_path());
+            let (start, end) = (points[0], points[points.len() - 1]);
+            if points.len() > 2 && (start.0 - end.0).hypot(start.1 - end.1) < 0.001 {
+                points.pop();
+                finish(&mut profiles, &mut chain, &mut points, true);
+            }
+        }
+    }
+    finish(&mut profiles, &mut chain, &mut points, false);
+    profiles
+}
+
+/// closed profiles with repeated passes over the same outline merged, keeping the deepest pass
+pub fn outlines<'a>(profiles: &'a [Profile<'a>]) -> Vec<&'a Profile<'a>> {
+    let mut outlines: Vec<&Profile> = Vec::new();
+    for p in profiles.iter().filter(|p| p.closed) {
+        match outlines.iter_mut().find(|o| o.same_outline(p) && o.moves[0].setup.tool == p.moves[0].setup.tool) {
+            Some(o) => if p.depth() < o.depth() { *o = p },
+            None => outlines.push(p),
+        }
+    }
+    outlines
+}
+
+/// an outline inside another outline is cutting a hole or pocket wall, otherwise it is the outside of a part
+pub fn is_inside(outline: &Profile, outlines: &[&Profile]) -> bool {
+    let (min, max) = outline.bounds();
+    outlines.iter().any(|o| {
+        let (omin, omax) = o.bounds();
+        !o.same_outline(outline) && omin.0 <= min.0 && omin.1 <= min.1 && omax.0 >= max.0 && omax.1 >= max.1
+            && o.contains(outline.points[0])
+    })
+}
+
+fn describe(outline: &Profile, inside: bool) -> String {
+    let (start, end) = outline.lines();
+    format!("{} profile at lines {}-{} with {}",
+        if inside { "inside" } else { "outside" }, start, end, outline.label())
+}
+
+/// with the spindle turning clockwise (M3), climb milling keeps the material on the tool's right, like G41.
+/// that's clockwise around the outside of a part and counterclockwise around a hole
+fn is_climb(outline: &Profile, inside: bool) -> bool {
+    let ccw = outline.area() > 0.0;
+    (ccw == inside) == (outline.moves[0].setup.spindle != Spindle::Ccw)
+}
+
+pub fn check_direction(moves: &[Move], thickness: Option<f32>, config_items: &HashMap<String,f32>, material: Option<&str>) -> Outcome {
+    let name = "Cut Direction";
+    let profiles = profiles(moves, thickness);
+    let outlines = outlines(&profiles);
+    let mut issues: Vec<Issue> = Vec::new();
+    let mut summary: Vec<String> = Vec::new();
+
+    for outline in &outlines {
+        let first = outline.moves[0];
+        let inside = is_inside(outline, &outlines);
+        let climb = is_climb(outline, inside);
+        let description = describe(outline, inside);
+        summary.push(format!("{}: {}",description,if climb { "climb" } else { "conventional" }));
+
+        let q = config::Qualifiers {
+            material,
+            tool_number: first.setup.tool_number,
+            diameter: first.setup.tool.size(),
+        };
+        let preference = config::lookup(config_items, "CUT_DIRECTION", &q);
+        if (preference > 0.0 && !climb) || (preference < 0.0 && climb) {
+            issues.push(Issue::new(Status::Warning, "wrong_direction",
+                format!("{} is cut {}, but {} is preferred{}",
+                    description,
+                    if climb { "climb" } else { "conventional" },
+                    if climb { "conventional" } else { "climb" },
+                    material.map_or(String::new(), |m| format!(" for {}",m)),
+                )
+            ).between(outline.lines()));
+        }
+    }
+
+    if summary.is_empty() {
+        return Outcome::new_full(name, Status::Pass, "no closed profiles detected".into());
+    }
+    Outcome::from_issues(name, issues, "every profile is cut in the preferred direction".into())
+        .with_details(summary.join("\n"))
+}
+
+/// cuts deeper than this are through the material, anything shallower leaves a skin on the table
+pub const THROUGH: f32 = 0.0005;
+
+/// pocket clearing passes, which have another pass less than a tool width away
+pub fn pocket_moves<'a>(ops: &[Operation<'a>]) -> Vec<&'a Move> {
+    ops.iter()
+        .flat_map(|op| op.levels.iter().map(move |l| (op, l)))
+        .filter_map(|(op, level)| op.diameter().map(|d| (d, passes::neighbour_distances(level, d * 2.0))))
+        .flat_map(|(diameter, distances)| distances.into_iter().filter(move |(_,d)| d.is_some_and(|d| d < diameter)))
+        .map(|(m,_)| m)
+        .collect()
+}
+
+/// pocket walls aren't meant to go through, and run alongside the passes clearing the pocket
+pub fn is_pocket_wall(outline: &Profile, pocket_moves: &[&Move]) -> bool {
+    outline.moves.iter().any(|m| pocket_moves.iter().any(|p| std::ptr::eq(*m, *p)))
+}
+
+/// stretches of a profile's lateral moves that stay above `depth`, as (first move, last move, length, lowest z)
+fn raised(outline: &Profile, depth: f32) -> Vec<(usize,usize,f32,f32)> {
+    let mut stretches: Vec<(usize,usize,f32,f32)> = Vec::new();
+    let mut open = false;
+    for (i, mv) in outline.moves.iter().enumerate() {
+        if mv.from.x == mv.to.x && mv.from.y == mv.to.y {
+            continue; //ramping up and down for tabs
+        }
+        let z = mv.from.z.into_iter().chain(mv.to.z).fold(f32::MIN, f32::max);
+        if z <= depth {
+            open = false;
+            continue;
+        }
+        match stretches.last_mut() {
+            Some(s) if open => {
+                s.1 = i;
+                s.2 += mv.length();
+                s.3 = s.3.min(z);
+            }
+            _ => stretches.push((i, i, mv.length(), z)),
+        }
+        open = true;
+    }
+    stretches
+}
+
+pub fn check_through_cut(ops: &[Operation], moves: &[Move], thickness: Option<f32>, max_tab_length: f32) -> Outcome {
+    let name = "Through Cut";
+    if thickness.is_none() {
+        return Outcome::skipped(name, Reason::NoThickness);
+    }
+    let profiles = profiles(moves, thickness);
+    let outlines = outlines(&profiles);
+    let mut issues: Vec<Issue> = Vec::new();
+    let mut summary: Vec<String> = Vec::new();
+    let pocket_moves = pocket_moves(ops);
+
+    for outline in &outlines {
+        if is_pocket_wall(outline, &pocket_moves) {
+            continue;
+        }
+        let description = describe(outline, is_inside(outline, &outlines));
+        let depth = outline.depth().unwrap_or(f32::MAX);
+        if depth > THROUGH {
+            summary.push(format!("{}: stops at z {}",description,depth));
+            issues.push(Issue::new(Status::Fail, "above_table",
+                format!("{} stays above the table, its deepest cut is at z {}",description,depth)
+            ).between(outline.lines()));
+            continue;
+        }
+        let mut tabs = 0;
+        for (first, last, length, z) in raised(outline, THROUGH) {
+            if length <= max_tab_length {
+                tabs += 1;
+                continue;
+            }
+            issues.push(Issue::new(Status::Fail, "long_tab",
+                format!("{} stays above the table for {:.2} in at lines {}-{}, at z {}",
+                    description,length,outline.moves[first].line,outline.moves[last].line,z,
+                )
+            ).between((outline.moves[first].line, outline.moves[last].line)));
+        }
+        summary.push(format!("{}: through{}",description,
+            match tabs { 0 => String::new(), 1 => ", 1 tab".into(), n => format!(", {} tabs",n) }
+        ));
+    }
+
+    if summary.is_empty() {
+        return Outcome::new_full(name, Status::Pass, "no closed profiles detected".into());
+    }
+    Outcome::from_issues(name, issues, "every profile cuts through the material".into())
+        .with_details(summary.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gcode::State;
+    use crate::Tool;
+
+    /// the corners of a square, in the order it's cut
+    fn square(min: (f32,f32), max: (f32,f32), clockwise: bool) -> Vec<(f32,f32)> {
+        if clockwise {
+            vec![(min.0, max.1), (max.0, max.1), (max.0, min.1), min]
+        } else {
+            vec![(max.0, min.1), (max.0, max.1), (min.0, max.1), min]
+        }
+    }
+
+    /// a 6x4 part with a 1x1 hole in it, cut with M3 in 0.5 thick stock
+    fn program(outside_clockwise: bool, hole_clockwise: bool) -> Vec<Move> {
+        program_to(outside_clockwise, hole_clockwise, 0.0)
+    }
+
+    /// the same part, with its outline cut down to `depth`
+    fn program_to(outside_clockwise: bool, hole_clockwise: bool, depth: f32) -> Vec<Move> {
+        let mut lines = vec!["G20 G90".to_string(), "S18000 M3".into()];
+        for (min, max, clockwise, z) in [((2.0, 1.0), (3.0, 2.0), hole_clockwise, 0.0), ((0.0, 0.0), (6.0, 4.0), outside_clockwise, depth)] {
+            lines.push("G0 Z1".into());
+            lines.push(format!("G0 X{} Y{}",min.0,min.1));
+            lines.push(format!("G1 Z{} F20",z));
+            lines.extend(square(min, max, clockwise).iter().map(|(x, y)| format!("G1 X{} Y{} F100",x,y)));
+        }
+        let mut state = State::new();
+        state.tool = Tool::Endmill(Some(0.25));
+        lines.iter().enumerate().filter_map(|(i, line)| state.apply(i + 1, line)).collect()
+    }
+
+    /// (inside, climb) for each outline, hole first
+    fn directions(moves: &[Move]) -> Vec<(bool,bool)> {
+        let profiles = profiles(moves, Some(0.5));
+        let outlines = outlines(&profiles);
+        outlines.iter().map(|o| {
+            let inside = is_inside(o, &outlines);
+            (inside, is_climb(o, inside))
+        }).collect()
+    }
+
+    #[test]
+    fn clockwise_outside_and_counterclockwise_inside_are_climb() {
+        assert_eq!(directions(&program(true, false)), vec![(true, true), (false, true)]);
+    }
+
+    #[test]
+    fn counterclockwise_outside_and_clockwise_inside_are_conventional() {
+        assert_eq!(directions(&program(false, true)), vec![(true, false), (false, false)]);
+    }
+
+    #[test]
+    fn an_outline_that_stops_above_the_table_fails() {
+        let through_cut = |moves: &[Move]| check_through_cut(&passes::operations(moves, Some(0.5), 20), moves, Some(0.5), 0.5);
+        assert_eq!(through_cut(&program(true, false)).status, Status::Pass);
+        let shallow = through_cut(&program_to(true, false, 0.1));
+        assert_eq!(shallow.status, Status::Fail);
+        assert_eq!(shallow.issue_kinds(), vec![Some("above_table")]);
+    }
+}
@@ -0,0 +1,662 @@
+use std::fs;
+use std::path::Path;
+use regex::Regex;
+use crate::gcode::Move;
+use crate::passes::Operation;
+use crate::profiles;
+use crate::{Issue, Outcome, Status, Tool};
+
+lazy_static! {
+    static ref SVG_ELEMENT_RE: Regex = Regex::new(r"<(/?)(svg|g|circle|ellipse|rect|line|polyline|polygon|path)\b([^>]*)>").unwrap();
+    static ref SVG_ATTRIBUTE_RE: Regex = Regex::new(r#"([A-Za-z][A-Za-z:-]*)\s*=\s*["']([^"']*)["']"#).unwrap();
+    static ref SVG_LENGTH_RE: Regex = Regex::new(r"^\s*(-?[0-9]*\.?[0-9]+(?:[eE][-+]?[0-9]+)?)\s*([a-z]*)").unwrap();
+    static ref SVG_TRANSFORM_RE: Regex = Regex::new(r"([A-Za-z]+)\s*\(([^)]*)\)").unwrap();
+    static ref SVG_TOKEN_RE: Regex = Regex::new(r"[MmLlHhVvCcSsQqTtAaZz]|-?(?:[0-9]+\.?[0-9]*|\.[0-9]+)(?:[eE][-+]?[0-9]+)?").unwrap();
+}
+
+type Loop = Vec<(f32,f32)>;
+/// a dxf polyline point and the bulge of the segment after it
+type Vertex = ((f32,f32),f32);
+/// an svg transform matrix, `[a, b, c, d, e, f]` mapping (x, y) to (ax + cy + e, bx + dy + f)
+type Transform = [f32;6];
+
+const IDENTITY: Transform = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// a closed shape in the reference drawing, or one cut by the toolpath, by its bounds in inches
+#[derive(Clone,Debug)]
+pub struct Feature {
+    pub min: (f32,f32),
+    pub max: (f32,f32),
+    pub hole: bool,
+    /// the first and last line it's cut on in the toolpath, `None` for the reference
+    pub lines: Option<(usize,usize)>,
+}
+impl Feature {
+    fn from_points(points: &[(f32,f32)], hole: bool, lines: Option<(usize,usize)>) -> Feature {
+        let (min, max) = points.iter().fold(((f32::MAX,f32::MAX),(f32::MIN,f32::MIN)), |(min,max),p| {
+            ((min.0.min(p.0), min.1.min(p.1)), (max.0.max(p.0), max.1.max(p.1)))
+        });
+        Feature { min, max, hole, lines }
+    }
+    pub fn center(&self) -> (f32,f32) {
+        ((self.min.0 + self.max.0) / 2.0, (self.min.1 + self.max.1) / 2.0)
+    }
+    pub fn size(&self) -> (f32,f32) {
+        (self.max.0 - self.min.0, self.max.1 - self.min.1)
+    }
+    /// where it's cut, like `lines 12-40`
+    pub fn place(&self) -> String {
+        match self.lines {
+            Some((first, last)) if first != last => format!("lines {}-{}",first,last),
+            Some((line, _)) => format!("line {}",line),
+            None => String::new(),
+        }
+    }
+    pub fn describe(&self) -> String {
+        let (w, h) = self.size();
+        let (x, y) = self.center();
+        format!("{} {:.3} x {:.3} at ({:.3}, {:.3})",if self.hole { "hole" } else { "outline" },w,h,x,y)
+    }
+}
+
+/// points along an arc, not including the start
+fn arc(center: (f32,f32), radius: f32, start: f32, sweep: f32) -> Loop {
+    let steps = ((sweep.abs() / 0.05).ceil() as usize).max(1);
+    (1..=steps)
+        .map(|i| start + sweep * i as f32 / steps as f32)
+        .map(|a| (center.0 + radius * a.cos(), center.1 + radius * a.sin()))
+        .collect()
+}
+
+/// points along a dxf polyline segment with a bulge, the tangent of a quarter of its sweep
+fn bulge(from: (f32,f32), to: (f32,f32), bulge: f32) -> Loop {
+    if bulge.abs() < 0.000001 {
+        return vec![to];
+    }
+    let sweep = 4.0 * bulge.atan();
+    let chord = (to.0 - from.0).hypot(to.1 - from.1);
+    let radius = chord / 2.0 / (sweep / 2.0).sin().abs();
+    //the center is off the middle of the chord, to the left when going counterclockwise
+    let offset = chord / 2.0 / (sweep / 2.0).tan();
+    let normal = (-(to.1 - from.1) / chord, (to.0 - from.0) / chord);
+    let center = ((from.0 + to.0) / 2.0 + normal.0 * offset, (from.1 + to.1) / 2.0 + normal.1 * offset);
+    let mut points = arc(center, radius, (from.1 - center.1).atan2(from.0 - center.0), sweep);
+    points.pop();
+    points.push(to);
+    points
+}
+
+fn near(a: (f32,f32), b: (f32,f32)) -> bool {
+    (a.0 - b.0).hypot(a.1 - b.1) < 0.001
+}
+
+/// joins open pieces end to end into closed loops, dropping anything left open
+fn join(mut loops: Vec<Loop>, mut pieces: Vec<Loop>) -> Vec<Loop> {
+    while let Some(mut chain) = pieces.pop() {
+        loop {
+            let end = chain[chain.len() - 1];
+            if chain.len() > 2 && near(chain[0], end) {
+                loops.push(chain);
+                break;
+            }
+            match pieces.iter().position(|p| near(p[0], end) || near(p[p.len() - 1], end)) {
+                Some(i) => {
+                    let mut next = pieces.remove(i);
+                    if !near(next[0], end) {
+                        next.reverse();
+                    }
+                    chain.extend(next.into_iter().skip(1));
+                }
+                None => break,
+            }
+        }
+    }
+    loops
+}
+
+fn read_dxf(contents: &str) -> Result<Vec<Loop>,String> {
+    let lines: Vec<&str> = contents.lines().map(|l| l.trim()).collect();
+    let pairs: Vec<(i32,&str)> = lines.chunks(2)
+        .filter_map(|c| if c.len() == 2 { c[0].parse::<i32>().ok().map(|code| (code, c[1])) } else { None })
+        .collect();
+    if pairs.is_empty() {
+        return Err("not a dxf file".into());
+    }
+    //$INSUNITS is 1 for inches, 4 for millimeters and 5 for centimeters
+    let scale = match pairs.windows(2).find(|w| w[0] == (9, "$INSUNITS")).map(|w| w[1].1) {
+        Some("4") => 1.0 / 25.4,
+        Some("5") => 1.0 / 2.54,
+        _ => 1.0,
+    };
+    let start = pairs.windows(2).position(|w| w[0] == (0, "SECTION") && w[1] == (2, "ENTITIES"))
+        .ok_or("no ENTITIES section in dxf file")?;
+
+    let mut entities: Vec<(&str,Vec<(i32,f32)>)> = Vec::new();
+    for (code, value) in &pairs[start + 2..] {
+        if *code == 0 {
+            if *value == "ENDSEC" {
+                break;
+            }
+            entities.push((value, Vec::new()));
+        } else if let (Some(entity), Ok(v)) = (entities.last_mut(), value.parse::<f32>()) {
+            entity.1.push((*code, v));
+        }
+    }
+
+    let mut loops: Vec<Loop> = Vec::new();
+    let mut pieces: Vec<Loop> = Vec::new();
+    let mut polyline: Option<(bool,Vec<Vertex>)> = None;
+    let finish_polyline = |vertices: Vec<Vertex>, closed: bool, loops: &mut Vec<Loop>, pieces: &mut Vec<Loop>| {
+        if vertices.len() < 2 {
+            return;
+        }
+        let mut points = vec![vertices[0].0];
+        let n = if closed { vertices.len() } else { vertices.len() - 1 };
+        for i in 0..n {
+            let (from, b) = vertices[i];
+            points.extend(bulge(from, vertices[(i + 1) % vertices.len()].0, b));
+        }
+        if closed { loops.push(points) } else { pieces.push(points) }
+    };
+    for (kind, codes) in entities {
+        let get = |code: i32| codes.iter().find(|c| c.0 == code).map_or(0.0, |c| c.1);
+        match kind {
+            "LINE" => pieces.push(vec![(get(10), get(20)), (get(11), get(21))]),
+            "CIRCLE" => {
+                let mut points = arc((get(10), get(20)), get(40), 0.0, std::f32::consts::TAU);
+                points.insert(0, points[points.len() - 1]);
+                loops.push(points);
+            }
+            "ARC" => {
+                let (center, radius) = ((get(10), get(20)), get(40));
+                let start = get(50).to_radians();
+                let mut sweep = get(51).to_radians() - start;
+                if sweep <= 0.0 {
+                    sweep += std::f32::consts::TAU;
+                }
+                let mut points = vec![(center.0 + radius * start.cos(), center.1 + radius * start.sin())];
+                points.extend(arc(center, radius, start, sweep));
+                pieces.push(points);
+            }
+            "LWPOLYLINE" => {
+                let mut vertices: Vec<Vertex> = Vec::new();
+                for (code, v) in &codes {
+                    match code {
+                        10 => vertices.push(((*v, 0.0), 0.0)),
+                        20 => if let Some(last) = vertices.last_mut() { last.0.1 = *v },
+                        42 => if let Some(last) = vertices.last_mut() { last.1 = *v },
+                        _ => {}
+                    }
+                }
+                finish_polyline(vertices, get(70) as i32 & 1 == 1, &mut loops, &mut pieces);
+            }
+            "POLYLINE" => polyline = Some((get(70) as i32 & 1 == 1, Vec::new())),
+            "VERTEX" => if let Some((_, vertices)) = polyline.as_mut() {
+                vertices.push(((get(10), get(20)), get(42)));
+            },
+            "SEQEND" => if let Some((closed, vertices)) = polyline.take() {
+                finish_polyline(vertices, closed, &mut loops, &mut pieces);
+            },
+            _ => {}
+        }
+    }
+    let mut loops = join(loops, pieces);
+    for l in loops.iter_mut() {
+        for p in l.iter_mut() {
+            *p = (p.0 * scale, p.1 * scale);
+        }
+    }
+    Ok(loops)
+}
+
+/// inches per svg user unit, from the width of the drawing and its viewbox
+fn svg_scale(attributes: &[(String,String)]) -> f32 {
+    let get = |name: &str| attributes.iter().find(|a| a.0 == name).map(|a| a.1.as_str());
+    let per_unit = |unit: &str| match unit {
+        "mm" => 1.0 / 25.4,
+        "cm" => 1.0 / 2.54,
+        "in" => 1.0,
+        "pt" => 1.0 / 72.0,
+        _ => 1.0 / 96.0,
+    };
+    let width = get("width").and_then(|w| SVG_LENGTH_RE.captures(w))
+        .and_then(|c| c[1].parse::<f32>().ok().map(|v| v * per_unit(&c[2])));
+    let view_width = get("viewBox")
+        .and_then(|v| v.split(|c: char| c == ',' || c.is_whitespace()).filter(|s| !s.is_empty()).nth(2))
+        .and_then(|v| v.parse::<f32>().ok());
+    match (width, view_width) {
+        (Some(w), Some(v)) if v > 0.0 => w / v,
+        _ => 1.0 / 96.0,
+    }
+}
+
+/// points along an svg elliptical arc, following the endpoint to center conversion in the svg spec
+fn svg_arc(from: (f32,f32), radii: (f32,f32), rotation: f32, large: bool, sweep: bool, to: (f32,f32)) -> Loop {
+    let (mut rx, mut ry) = (radii.0.abs(), radii.1.abs());
+    if rx < 0.000001 || ry < 0.000001 || near(from, to) {
+        return vec![to];
+    }
+    let (sin, cos) = rotation.to_radians().sin_cos();
+    let (dx, dy) = ((from.0 - to.0) / 2.0, (from.1 - to.1) / 2.0);
+    let (x1, y1) = (cos * dx + sin * dy, -sin * dx + cos * dy);
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+    let numerator = (rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1).max(0.0);
+    let mut k = (numerator / (rx * rx * y1 * y1 + ry * ry * x1 * x1)).sqrt();
+    if large == sweep {
+        k = -k;
+    }
+    let (cx1, cy1) = (k * rx * y1 / ry, -k * ry * x1 / rx);
+    let center = (cos * cx1 - sin * cy1 + (from.0 + to.0) / 2.0, sin * cx1 + cos * cy1 + (from.1 + to.1) / 2.0);
+    let start = ((y1 - cy1) / ry).atan2((x1 - cx1) / rx);
+    let mut delta = ((-y1 - cy1) / ry).atan2((-x1 - cx1) / rx) - start;
+    if sweep && delta < 0.0 {
+        delta += std::f32::consts::TAU;
+    } else if !sweep && delta > 0.0 {
+        delta -= std::f32::consts::TAU;
+    }
+    let mut points: Loop = arc((0.0, 0.0), 1.0, start, delta).into_iter()
+        .map(|(ux, uy)| (center.0 + cos * rx * ux - sin * ry * uy, center.1 + sin * rx * ux + cos * ry * uy))
+        .collect();
+    points.pop();
+    points.push(to);
+    points
+}
+
+fn bezier(points: &[(f32,f32)]) -> Loop {
+    (1..=8).map(|i| {
+        let t = i as f32 / 8.0;
+        let mut p = points.to_vec();
+        while p.len() > 1 {
+            p = p.windows(2).map(|w| (w[0].0 + (w[1].0 - w[0].0) * t, w[0].1 + (w[1].1 - w[0].1) * t)).collect();
+        }
+        p[0]
+    }).collect()
+}
+
+/// svg path data, as closed loops and open pieces
+fn svg_path(d: &str, loops: &mut Vec<Loop>, pieces: &mut Vec<Loop>) {
+    let tokens: Vec<&str> = SVG_TOKEN_RE.find_iter(d).map(|m| m.as_str()).collect();
+    let mut i = 0;
+    let mut command = 'M';
+    let mut current: Loop = Vec::new();
+    let mut pos = (0.0, 0.0);
+    let mut control = (0.0, 0.0);
+    let mut finish = |current: &mut Loop, closed: bool| {
+        if current.len() > 1 {
+            if closed { loops.push(std::mem::take(current)) } else { pieces.push(std::mem::take(current)) }
+        }
+        current.clear();
+    };
+    while i < tokens.len() {
+        if let Some(c) = tokens[i].chars().next().filter(|c| c.is_ascii_alphabetic()) {
+            command = c;
+            i += 1;
+            if c == 'Z' || c == 'z' {
+                if let Some(first) = current.first().copied() {
+                    pos = first;
+                    current.push(first);
+                }
+                finish(&mut current, true);
+                continue;
+            }
+        }
+        let count = match command.to_ascii_uppercase() { 'H' | 'V' => 1, 'M' | 'L' | 'T' => 2, 'S' | 'Q' => 4, 'C' => 6, 'A' => 7, _ => 0 };
+        let numbers: Vec<f32> = tokens[i..].iter().take(count).map_while(|t| t.parse::<f32>().ok()).collect();
+        if count == 0 || numbers.len() < count {
+            break;
+        }
+        i += count;
+        let relative = command.is_ascii_lowercase();
+        let point = |x: f32, y: f32| if relative { (pos.0 + x, pos.1 + y) } else { (x, y) };
+        let reflected = (2.0 * pos.0 - control.0, 2.0 * pos.1 - control.1);
+        let (to, new_control, path) = match command.to_ascii_uppercase() {
+            'M' => {
+                finish(&mut current, false);
+                let to = point(numbers[0], numbers[1]);
+                current.push(to);
+                //coordinates after a move are lines
+                command = if relative { 'l' } else { 'L' };
+                (to, to, Vec::new())
+            }
+            'L' => { let to = point(numbers[0], numbers[1]); (to, to, vec![to]) }
+            'H' => { let to = (if relative { pos.0 + numbers[0] } else { numbers[0] }, pos.1); (to, to, vec![to]) }
+            'V' => { let to = (pos.0, if relative { pos.1 + numbers[0] } else { numbers[0] }); (to, to, vec![to]) }
+            'C' => {
+                let (c1, c2, to) = (point(numbers[0], numbers[1]), point(numbers[2], numbers[3]), point(numbers[4], numbers[5]));
+                (to, c2, bezier(&[pos, c1, c2, to]))
+            }
+            'S' => {
+                let (c2, to) = (point(numbers[0], numbers[1]), point(numbers[2], numbers[3]));
+                (to, c2, bezier(&[pos, reflected, c2, to]))
+            }
+            'Q' => {
+                let (c, to) = (point(numbers[0], numbers[1]), point(numbers[2], numbers[3]));
+                (to, c, bezier(&[pos, c, to]))
+            }
+            'T' => {
+                let to = point(numbers[0], numbers[1]);
+                (to, reflected, bezier(&[pos, reflected, to]))
+            }
+            _ => {
+                let to = point(numbers[5], numbers[6]);
+                (to, to, svg_arc(pos, (numbers[0], numbers[1]), numbers[2], numbers[3] != 0.0, numbers[4] != 0.0, to))
+            }
+        };
+        if current.is_empty() {
+            current.push(pos);
+        }
+        current.extend(path);
+        pos = to;
+        control = new_control;
+    }
+    finish(&mut current, false);
+}
+
+/// applies `inner` and then `outer`
+fn compose(outer: Transform, inner: Transform) -> Transform {
+    let [a, b, c, d, e, f] = outer;
+    let [p, q, r, s, t, u] = inner;
+    [a * p + c * q, b * p + d * q, a * r + c * s, b * r + d * s, a * t + c * u + e, b * t + d * u + f]
+}
+
+fn apply(m: Transform, p: (f32,f32)) -> (f32,f32) {
+    (m[0] * p.0 + m[2] * p.1 + m[4], m[1] * p.0 + m[3] * p.1 + m[5])
+}
+
+/// an svg `transform` attribute, like `translate(10 20) scale(2)`
+fn svg_transform(text: &str) -> Result<Transform,String> {
+    let mut transform = IDENTITY;
+    let mut rest = text.to_string();
+    for c in SVG_TRANSFORM_RE.captures_iter(text) {
+        rest = rest.replacen(&c[0], "", 1);
+        let n: Vec<f32> = SVG_TOKEN_RE.find_iter(&c[2]).filter_map(|m| m.as_str().parse::<f32>().ok()).collect();
+        let (sin, cos) = n.first().map_or(0.0, |a| a.to_radians()).sin_cos();
+        let step = match (&c[1], n.as_slice()) {
+            ("matrix", [a, b, c, d, e, f]) => [*a, *b, *c, *d, *e, *f],
+            ("translate", [x]) => [1.0, 0.0, 0.0, 1.0, *x, 0.0],
+            ("translate", [x, y]) => [1.0, 0.0, 0.0, 1.0, *x, *y],
+            ("scale", [x]) => [*x, 0.0, 0.0, *x, 0.0, 0.0],
+            ("scale", [x, y]) => [*x, 0.0, 0.0, *y, 0.0, 0.0],
+            ("rotate", [_]) => [cos, sin, -sin, cos, 0.0, 0.0],
+            ("rotate", [_, x, y]) => compose(compose([1.0, 0.0, 0.0, 1.0, *x, *y], [cos, sin, -sin, cos, 0.0, 0.0]), [1.0, 0.0, 0.0, 1.0, -x, -y]),
+            ("skewX", [a]) => [1.0, 0.0, a.to_radians().tan(), 1.0, 0.0, 0.0],
+            ("skewY", [a]) => [1.0, a.to_radians().tan(), 0.0, 1.0, 0.0, 0.0],
+            _ => return Err(format!("can't read svg transform '{}'",&c[0])),
+        };
+        transform = compose(transform, step);
+    }
+    if rest.chars().any(|c| !c.is_whitespace() && c != ',') {
+        return Err(format!("can't read svg transform '{}'",text));
+    }
+    Ok(transform)
+}
+
+/// svg shapes, with the transforms on them and the groups they're in applied
+fn read_svg(contents: &str) -> Result<Vec<Loop>,String> {
+    let mut loops: Vec<Loop> = Vec::new();
+    let mut pieces: Vec<Loop> = Vec::new();
+    let mut scale = 1.0 / 96.0;
+    let mut found_svg = false;
+    //the transform of each group the elements are in, outermost first
+    let mut groups: Vec<Transform> = Vec::new();
+    for element in SVG_ELEMENT_RE.captures_iter(contents) {
+        if &element[1] == "/" {
+            if &element[2] == "g" {
+                groups.pop();
+            }
+            continue;
+        }
+        let attributes: Vec<(String,String)> = SVG_ATTRIBUTE_RE.captures_iter(&element[3])
+            .map(|a| (a[1].to_string(), a[2].to_string()))
+            .collect();
+        let text = |name: &str| attributes.iter().find(|a| a.0 == name).map_or("", |a| a.1.as_str());
+        let get = |name: &str| SVG_LENGTH_RE.captures(text(name)).and_then(|c| c[1].parse::<f32>().ok()).unwrap_or(0.0);
+        let numbers = |name: &str| SVG_TOKEN_RE.find_iter(text(name)).filter_map(|m| m.as_str().parse::<f32>().ok()).collect::<Vec<f32>>();
+        let transform = compose(groups.last().copied().unwrap_or(IDENTITY), svg_transform(text("transform"))?);
+        let (mut closed, mut open): (Vec<Loop>,Vec<Loop>) = (Vec::new(), Vec::new());
+        match &element[2] {
+            "svg" => {
+                found_svg = true;
+                scale = svg_scale(&attributes);
+            }
+            "g" => if !element[3].trim_end().ends_with('/') {
+                groups.push(transform);
+            },
+            "circle" | "ellipse" => {
+                let (rx, ry) = if &element[2] == "circle" { (get("r"), get("r")) } else { (get("rx"), get("ry")) };
+                let mut points: Loop = arc((0.0, 0.0), 1.0, 0.0, std::f32::consts::TAU).into_iter()
+                    .map(|(x, y)| (get("cx") + rx * x, get("cy") + ry * y))
+                    .collect();
+                points.insert(0, points[points.len() - 1]);
+                closed.push(points);
+            }
+            "rect" => {
+                let (x, y, w, h) = (get("x"), get("y"), get("width"), get("height"));
+                closed.push(vec![(x, y), (x + w, y), (x + w, y + h), (x, y + h), (x, y)]);
+            }
+            "line" => open.push(vec![(get("x1"), get("y1")), (get("x2"), get("y2"))]),
+            "polyline" | "polygon" => {
+                let mut points: Loop = numbers("points").chunks(2).filter(|c| c.len() == 2).map(|c| (c[0], c[1])).collect();
+                if points.len() < 2 {
+                    continue;
+                }
+                if &element[2] == "polygon" {
+                    points.push(points[0]);
+                    closed.push(points);
+                } else {
+                    open.push(points);
+                }
+            }
+            _ => svg_path(text("d"), &mut closed, &mut open),
+        }
+        let placed = |shapes: Vec<Loop>| shapes.into_iter().map(|l| l.into_iter().map(|p| apply(transform, p)).collect::<Loop>());
+        loops.extend(placed(closed));
+        pieces.extend(placed(open));
+    }
+    if !found_svg {
+        return Err("not an svg file".into());
+    }
+    //svg y goes down the page
+    let mut loops = join(loops, pieces);
+    for l in loops.iter_mut() {
+        for p in l.iter_mut() {
+            *p = (p.0 * scale, -p.1 * scale);
+        }
+    }
+    Ok(loops)
+}
+
+fn contains(points: &[(f32,f32)], p: (f32,f32)) -> bool {
+    let n = points.len();
+    let mut inside = false;
+    for i in 0..n {
+        let (a, b) = (points[i], points[(i + n - 1) % n]);
+        if (a.1 > p.1) != (b.1 > p.1) && p.0 < (b.0 - a.0) * (p.1 - a.1) / (b.1 - a.1) + a.0 {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// the closed shapes in a dxf or svg drawing. shapes inside an odd number of others are holes
+pub fn read(path: &Path) -> Result<Vec<Feature>,String> {
+    let contents = fs::read_to_string(path).map_err(|_| format!("couldn't read reference '{}'",path.display()))?;
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+    let loops = match extension.as_deref() {
+        Some("dxf") => read_dxf(&contents)?,
+        Some("svg") => read_svg(&contents)?,
+        _ => return Err(format!("reference '{}' isn't a .dxf or .svg file",path.display())),
+    };
+    let loops: Vec<Loop> = loops.into_iter().filter(|l| l.len() > 2).collect();
+    Ok(loops.iter().map(|l| {
+        let inside = loops.iter().filter(|o| !std::ptr::eq(*o, l) && contains(o, l[0])).count();
+        Feature::from_points(l, inside % 2 == 1, None)
+    }).collect())
+}
+
+/// the part shapes cut by the toolpath: through-cut profiles with the tool radius taken off, and drilled holes
+pub fn features(ops: &[Operation], moves: &[Move], thickness: Option<f32>) -> Vec<Feature> {
+    let mut features: Vec<Feature> = Vec::new();
+    let profiles = profiles::profiles(moves, thickness);
+    let outlines = profiles::outlines(&profiles);
+    let pocket_moves = profiles::pocket_moves(ops);
+    for outline in &outlines {
+        if profiles::is_pocket_wall(outline, &pocket_moves) || outline.depth().is_none_or(|d| d > profiles::THROUGH) {
+            continue;
+        }
+        let hole = profiles::is_inside(outline, &outlines);
+        //the tool is outside the part and inside holes
+        let r = outline.moves[0].setup.tool.size().unwrap_or(0.0) / 2.0 * if hole { -1.0 } else { 1.0 };
+        let (min, max) = outline.bounds();
+        features.push(Feature {
+            min: (min.0 + r, min.1 + r),
+            max: (max.0 - r, max.1 - r),
+            hole,
+            lines: Some(outline.lines()),
+        });
+    }
+    for mv in moves.iter().filter(|m| m.is_plunge() && m.to.z.is_some_and(|z| z <= profiles::THROUGH)) {
+        if let (Tool::Drill(size), Some(x), Some(y)) = (&mv.setup.tool, mv.to.x, mv.to.y) {
+            let r = size.unwrap_or(0.0) / 2.0;
+            let drilled = Feature { min: (x - r, y - r), max: (x + r, y + r), hole: true, lines: Some((mv.line, mv.line)) };
+            //pecking the same hole again
+            if !features.iter().any(|f| f.hole && near(f.center(), drilled.center()) && near(f.size(), drilled.size())) {
+                features.push(drilled);
+            }
+        }
+    }
+    features
+}
+
+pub fn check_reference(reference: Result<Vec<Feature>,String>, cut: &[Feature], tolerance: f32) -> Outcome {
+    let name = "Reference";
+    let mut reference = match reference {
+        Ok(r) => r,
+        Err(error) => return Outcome::new(name).set(Status::Error, error),
+    };
+    if reference.is_empty() {
+        return Outcome::new(name).set(Status::Error, "no closed shapes found in the reference".into());
+    }
+    let mut details: Vec<String> = Vec::new();
+
+    //the drawing can be anywhere, so line up the part outlines. the offset check takes care of where the part is
+    let outer: Vec<&Feature> = reference.iter().filter(|f| !f.hole).collect();
+    let cut_outer: Vec<&Feature> = cut.iter().filter(|f| !f.hole).collect();
+    if let ([r], [c]) = (outer.as_slice(), cut_outer.as_slice()) {
+        let shift = (c.min.0 - r.min.0, c.min.1 - r.min.1);
+        for f in reference.iter_mut() {
+            f.min = (f.min.0 + shift.0, f.min.1 + shift.1);
+            f.max = (f.max.0 + shift.0, f.max.1 + shift.1);
+        }
+        details.push(format!("reference moved by ({:.3}, {:.3}) to line up with the part outline",shift.0,shift.1));
+    }
+
+    let close = |a: (f32,f32), b: (f32,f32)| (a.0 - b.0).abs() <= tolerance && (a.1 - b.1).abs() <= tolerance;
+    let mut issues: Vec<Issue> = Vec::new();
+    let mut unmatched: Vec<&Feature> = cut.iter().collect();
+    let mut missing: Vec<&Feature> = Vec::new();
+    //exact matches first, so a wrong feature doesn't take the place of a right one
+    for r in &reference {
+        match unmatched.iter().position(|c| c.hole == r.hole && close(c.center(), r.center()) && close(c.size(), r.size())) {
+            Some(i) => { unmatched.remove(i); }
+            None => missing.push(r),
+        }
+    }
+    let mut unexplained: Vec<&Feature> = Vec::new();
+    for r in missing {
+        let same_place = unmatched.iter().position(|c| c.hole == r.hole && close(c.center(), r.center()));
+        let same_size = unmatched.iter().enumerate()
+            .filter(|(_,c)| c.hole == r.hole && close(c.size(), r.size()))
+            .min_by(|a, b| {
+                let d = |c: &Feature| (c.center().0 - r.center().0).hypot(c.center().1 - r.center().1);
+                d(a.1).total_cmp(&d(b.1))
+            })
+            .map(|(i,_)| i);
+        if let Some(i) = same_place {
+            let c = unmatched.remove(i);
+            let ((w, h), (rw, rh)) = (c.size(), r.size());
+            issues.push(Issue { lines: c.lines, ..Issue::new(Status::Fail, "wrong_size",
+                format!("wrong size: {} at {} is {:.3} x {:.3}, the reference is {:.3} x {:.3}",
+                    if r.hole { "hole" } else { "outline" },c.place(),w,h,rw,rh)
+            )});
+        } else if let Some(i) = same_size {
+            let c = unmatched.remove(i);
+            let ((x, y), (rx, ry)) = (c.center(), r.center());
+            issues.push(Issue { lines: c.lines, ..Issue::new(Status::Fail, "wrong_position",
+                format!("wrong position: {} at {} is centered at ({:.3}, {:.3}), the reference is at ({:.3}, {:.3})",
+                    if r.hole { "hole" } else { "outline" },c.place(),x,y,rx,ry)
+            )});
+        } else {
+            unexplained.push(r);
+        }
+    }
+    for r in unexplained {
+        issues.push(Issue::new(Status::Fail, "missing", format!("missing {}",r.describe())));
+    }
+    for c in unmatched {
+        issues.push(Issue { lines: c.lines, ..Issue::new(Status::Fail, "extra", format!("extra {} cut at {}",c.describe(),c.place())) });
+    }
+
+    let holes = reference.iter().filter(|f| f.hole).count();
+    details.push(format!("reference has {} outlines and {} holes, toolpath cuts {} outlines and {} holes",
+        reference.len() - holes, holes, cut.iter().filter(|f| !f.hole).count(), cut.iter().filter(|f| f.hole).count(),
+    ));
+    Outcome::from_issues(name, issues, format!("toolpath matches the reference within {} in",tolerance))
+        .with_details(details.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the bounds of each loop, to the nearest thousandth of an inch
+    fn bounds(loops: &[Loop]) -> Vec<((f32,f32),(f32,f32))> {
+        let round = |v: f32| (v * 1000.0).round() / 1000.0;
+        loops.iter().map(|l| {
+            let f = Feature::from_points(l, false, None);
+            ((round(f.min.0), round(f.min.1)), (round(f.max.0), round(f.max.1)))
+        }).collect()
+    }
+
+    #[test]
+    fn reads_dxf_circles_polylines_and_joined_lines_in_millimeters() {
+        let mut dxf = vec!["0", "SECTION", "2", "HEADER", "9", "$INSUNITS", "70", "4", "0", "ENDSEC", "0", "SECTION", "2", "ENTITIES"];
+        dxf.extend(["0", "CIRCLE", "10", "25.4", "20", "25.4", "40", "12.7"]);
+        dxf.extend(["0", "LWPOLYLINE", "70", "1", "10", "0", "20", "0", "10", "101.6", "20", "0", "10", "101.6", "20", "50.8", "10", "0", "20", "50.8"]);
+        for (x1, y1, x2, y2) in [("0", "0", "25.4", "0"), ("25.4", "25.4", "25.4", "0"), ("25.4", "25.4", "0", "0")] {
+            dxf.extend(["0", "LINE", "10", x1, "20", y1, "11", x2, "21", y2]);
+        }
+        dxf.extend(["0", "ENDSEC", "0", "EOF"]);
+        let loops = read_dxf(&dxf.join("\n")).unwrap();
+        assert_eq!(bounds(&loops), vec![((0.5, 0.5), (1.5, 1.5)), ((0.0, 0.0), (4.0, 2.0)), ((0.0, 0.0), (1.0, 1.0))]);
+    }
+
+    #[test]
+    fn svg_transforms_are_applied_through_groups() {
+        let svg = r#"<svg width="4in" height="2in" viewBox="0 0 4 2">
+            <rect x="0" y="0" width="4" height="2"/>
+            <g transform="translate(1, 0.5)">
+                <circle cx="0" cy="0" r="0.25" transform="scale(2)"/>
+                <g transform="matrix(1 0 0 1 2 0)"><path d="M0 0 h0.5 v0.5 h-0.5 z"/></g>
+            </g>
+            <rect x="0" y="0" width="0.5" height="0.25" transform="rotate(90)"/>
+        </svg>"#;
+        let loops = read_svg(svg).unwrap();
+        assert_eq!(bounds(&loops), vec![
+            ((0.0, -2.0), (4.0, 0.0)),
+            ((0.5, -1.0), (1.5, 0.0)),
+            ((3.0, -1.0), (3.5, -0.5)),
+            ((-0.25, -0.5), (0.0, 0.0)),
+        ]);
+    }
+
+    #[test]
+    fn svg_transforms_that_cant_be_read_are_an_error() {
+        let svg = r#"<svg width="4in" viewBox="0 0 4 2"><rect width="1" height="1" transform="shear(2)"/></svg>"#;
+        assert_eq!(read_svg(svg).err(), Some("can't read svg transform 'shear(2)'".into()));
+    }
+}
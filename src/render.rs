@@ -0,0 +1,111 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use crate::gcode::{Motion, Move};
+
+/// moves drawn in one colour
+pub struct Layer<'a> {
+    pub moves: &'a [Move],
+    pub color: &'a str,
+    pub label: String,
+}
+
+/// a box drawn around part of the plot, in inches
+pub type Highlight = ((f32,f32),(f32,f32));
+
+fn path(points: &[(f32,f32)]) -> String {
+    let mut d = String::new();
+    for (i, (x, y)) in points.iter().enumerate() {
+        //svg y goes down the page
+        write!(d, "{}{:.4},{:.4} ", if i == 0 { "M" } else { "L" }, x, -y).unwrap();
+    }
+    d
+}
+
+/// a top down plot of the toolpath: cutting moves solid, rapids dashed, plunges as circles the size of the tool,
+/// with highlighted areas in red
+pub fn svg(layers: &[Layer], thickness: Option<f32>, highlights: &[Highlight]) -> String {
+    let mut min = (f32::MAX, f32::MAX);
+    let mut max = (f32::MIN, f32::MIN);
+    let mut body = String::new();
+    for layer in layers {
+        let mut cuts: Vec<Vec<(f32,f32)>> = Vec::new();
+        let mut rapids = String::new();
+        let mut plunges = String::new();
+        let mut cutting = false;
+        for mv in layer.moves {
+            let (Some(x0), Some(y0)) = (mv.from.x, mv.from.y) else { continue };
+            let points = mv.xy_path();
+            for p in std::iter::once((x0, y0)).chain(points.iter().copied()) {
+                min = (min.0.min(p.0), min.1.min(p.1));
+                max = (max.0.max(p.0), max.1.max(p.1));
+            }
+            if mv.motion == Motion::Rapid {
+                cutting = false;
+                if points.last().is_some_and(|p| *p != (x0, y0)) {
+                    rapids += &path(&[(x0, y0), points[points.len() - 1]]);
+                }
+            } else if mv.is_cutting(thickness) && mv.is_plunge() {
+                let r = mv.setup.tool.size().unwrap_or(0.05) / 2.0;
+                writeln!(plunges, "<circle cx=\"{:.4}\" cy=\"{:.4}\" r=\"{:.4}\" stroke-width=\"0.01\"/>",x0,-y0,r).unwrap();
+            } else if mv.is_cutting(thickness) {
+                if !cutting {
+                    cuts.push(vec![(x0, y0)]);
+                }
+                cutting = true;
+                cuts.last_mut().unwrap().extend(points);
+            } else {
+                cutting = false;
+            }
+        }
+        let cuts: String = cuts.iter().map(|c| path(c)).collect();
+        writeln!(body, "<g stroke=\"{}\" fill=\"none\" stroke-linecap=\"round\" stroke-linejoin=\"round\">",layer.color).unwrap();
+        writeln!(body, "<path d=\"{}\" stroke-width=\"0.01\" stroke-dasharray=\"0.05 0.05\" opacity=\"0.5\"/>",rapids.trim()).unwrap();
+        writeln!(body, "<path d=\"{}\" stroke-width=\"0.02\"/>",cuts.trim()).unwrap();
+        body += &plunges;
+        writeln!(body, "</g>").unwrap();
+    }
+    for (lo, hi) in highlights {
+        writeln!(body, "<rect x=\"{:.4}\" y=\"{:.4}\" width=\"{:.4}\" height=\"{:.4}\" fill=\"red\" fill-opacity=\"0.15\" stroke=\"red\" stroke-width=\"0.01\"/>",
+            lo.0, -hi.1, hi.0 - lo.0, hi.1 - lo.1).unwrap();
+    }
+    if min.0 > max.0 {
+        min = (0.0, 0.0);
+        max = (1.0, 1.0);
+    }
+    let margin = 0.1 * (max.0 - min.0).max(max.1 - min.1).max(1.0);
+    let (x, y) = (min.0 - margin, -max.1 - margin * 2.0);
+    let (w, h) = (max.0 - min.0 + margin * 2.0, max.1 - min.1 + margin * 3.0);
+    for (i, layer) in layers.iter().enumerate() {
+        writeln!(body, "<text x=\"{:.4}\" y=\"{:.4}\" font-size=\"{:.4}\" font-family=\"sans-serif\" fill=\"{}\">{}</text>",
+            x + margin / 2.0, y + margin * 0.6 * (i + 1) as f32, margin * 0.4, layer.color, layer.label.replace('&', "&amp;").replace('<', "&lt;")).unwrap();
+    }
+    format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.3}in\" height=\"{:.3}in\" viewBox=\"{:.4} {:.4} {:.4} {:.4}\">\n{}</svg>\n",
+        w, h, x, y, w, h, body)
+}
+
+pub fn write(path: &Path, svg: &str) -> Result<(),String> {
+    fs::write(path, svg).map_err(|_| format!("couldn't write plot '{}'",path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gcode::State;
+    use crate::Tool;
+
+    #[test]
+    fn plots_cuts_plunges_rapids_and_highlights() {
+        let mut state = State::new();
+        state.tool = Tool::Endmill(Some(0.25));
+        let lines = ["G20 G90", "G0 X0 Y0 Z1", "G1 Z0 F20", "G1 X1 F100", "G1 Y1", "G0 Z1", "G0 X2 Y2"];
+        let moves: Vec<Move> = lines.iter().enumerate().filter_map(|(i, line)| state.apply(i + 1, line)).collect();
+        let plot = svg(&[Layer { moves: &moves, color: "black", label: "a<b>.nc".into() }], Some(0.5), &[((0.5, 0.5), (1.0, 1.5))]);
+        assert!(plot.contains("<path d=\"M0.0000,-0.0000 L1.0000,-0.0000 L1.0000,-1.0000\" stroke-width=\"0.02\"/>"));
+        assert!(plot.contains("<circle cx=\"0.0000\" cy=\"-0.0000\" r=\"0.1250\""));
+        assert!(plot.contains("stroke-dasharray=\"0.05 0.05\""));
+        assert!(plot.contains("M1.0000,-1.0000 L2.0000,-2.0000"));
+        assert!(plot.contains("<rect x=\"0.5000\" y=\"-1.5000\" width=\"0.5000\" height=\"1.0000\""));
+        assert!(plot.contains(">a&lt;b>.nc</text>"));
+    }
+}
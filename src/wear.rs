@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use crate::config;
+use crate::gcode::{Motion, Move};
+use crate::passes::{self, Operation};
+use crate::{Issue, Outcome, Reason, Status, Tool};
+
+/// how much cutting one tool does in a program
+#[derive(Clone,Debug)]
+pub struct Usage {
+    pub tool: String,
+    pub tool_number: Option<u32>,
+    pub diameter: Option<f32>,
+    /// inches of cutting moves
+    pub distance: f32,
+    /// cubic inches of material removed, roughly
+    pub volume: f32,
+}
+
+fn entry<'a>(usages: &'a mut Vec<Usage>, mv: &Move) -> &'a mut Usage {
+    let tool = mv.tool_label();
+    match usages.iter().position(|u| u.tool == tool) {
+        Some(i) => &mut usages[i],
+        None => {
+            usages.push(Usage { tool, tool_number: mv.setup.tool_number, diameter: mv.setup.tool.size(), distance: 0.0, volume: 0.0 });
+            usages.last_mut().unwrap()
+        }
+    }
+}
+
+pub fn usage(ops: &[Operation], moves: &[Move], thickness: Option<f32>) -> Vec<Usage> {
+    let mut usages: Vec<Usage> = Vec::new();
+    for mv in moves.iter().filter(|m| m.is_cutting(thickness)) {
+        let usage = entry(&mut usages, mv);
+        usage.distance += mv.length();
+        //drills remove a cylinder as deep as they go into the material
+        if let (Tool::Drill(Some(d)), Some(z0), Some(z1)) = (&mv.setup.tool, mv.from.z, mv.to.z) {
+            let top = thickness.map_or(z0, |t| z0.min(t));
+            usage.volume += std::f32::consts::PI * d * d / 4.0 * (top - z1).max(0.0);
+        }
+    }
+    //end mills remove however much of their width is engaged, as deep as the cut above them,
+    //for every sideways cut and not just the heights with enough moves to count as passes
+    for op in ops {
+        let diameter = if let Some(d) = op.diameter() { d } else { continue; };
+        let mut above = thickness;
+        for level in passes::cut_heights(&op.moves, thickness) {
+            let depth = above.map_or(0.0, |a| (a - level.z).max(0.0));
+            above = Some(level.z);
+            for (mv, nearest) in passes::neighbour_distances(&level, diameter) {
+                let width = nearest.map_or(diameter, |n| n.min(diameter));
+                entry(&mut usages, mv).volume += mv.length() * width * depth;
+            }
+            //arcs aren't compared with their neighbours, so they're taken to use the whole width
+            for mv in level.moves.iter().filter(|m| m.motion != Motion::Linear) {
+                entry(&mut usages, mv).volume += mv.length() * diameter * depth;
+            }
+        }
+    }
+    usages
+}
+
+/// several files can be validated at once, but only one at a time can read or rewrite the ledger
+static LEDGER: Mutex<()> = Mutex::new(());
+
+/// past cutting distance for each tool serial, kept as lines of `serial program distance volume`
+fn read_ledger(path: &Path) -> Vec<(u32,String,f32,f32)> {
+    let contents = fs::read_to_string(path).unwrap_or_default();
+    contents.lines().filter(|l| !l.starts_with("//")).filter_map(|l| {
+        let fields: Vec<&str> = l.split('\t').collect();
+        if fields.len() != 4 {
+            return None;
+        }
+        Some((fields[0].parse().ok()?, fields[1].into(), fields[2].parse().ok()?, fields[3].parse().ok()?))
+    }).collect()
+}
+
+fn write_ledger(path: &Path, entries: &[(u32,String,f32,f32)]) -> std::io::Result<()> {
+    let mut contents = String::from("//tool serial, program, cutting distance (in), volume removed (cubic in)\n");
+    for (serial, program, distance, volume) in entries {
+        contents += &format!("{}\t{}\t{:.1}\t{:.3}\n",serial,program,distance,volume);
+    }
+    fs::write(path, contents)
+}
+
+/// the serial number each tool is tracked by in the ledger, or 0 if it isn't tracked
+fn serial(usage: &Usage, config_items: &HashMap<String,f32>, material: Option<&str>) -> u32 {
+    let q = config::Qualifiers {
+        material,
+        tool_number: usage.tool_number,
+        diameter: usage.diameter,
+    };
+    config::lookup(config_items, "TOOL_SERIAL", &q) as u32
+}
+
+/// this program's cutting distance and volume for each serial, adding up tools that share one
+fn by_serial(usages: &[Usage], config_items: &HashMap<String,f32>, material: Option<&str>) -> Vec<(u32,f32,f32)> {
+    let mut totals: Vec<(u32,f32,f32)> = Vec::new();
+    for usage in usages {
+        let serial = serial(usage, config_items, material);
+        if serial == 0 {
+            continue;
+        }
+        match totals.iter_mut().find(|t| t.0 == serial) {
+            Some(total) => { total.1 += usage.distance; total.2 += usage.volume; }
+            None => totals.push((serial, usage.distance, usage.volume)),
+        }
+    }
+    totals
+}
+
+/// adds this program's cutting to the ledger. validating the same program again replaces its old entries
+/// instead of counting them twice
+pub fn record(usages: &[Usage], config_items: &HashMap<String,f32>, material: Option<&str>, ledger: &Path, program: &Path) -> Result<(),String> {
+    let program = program.display().to_string();
+    let _lock = LEDGER.lock().unwrap();
+    let mut entries = read_ledger(ledger);
+    entries.retain(|e| e.1 != program);
+    entries.extend(by_serial(usages, config_items, material).into_iter().map(|(serial, distance, volume)| (serial, program.clone(), distance, volume)));
+    write_ledger(ledger, &entries).map_err(|_| format!("couldn't write tool ledger '{}'",ledger.display()))
+}
+
+pub fn check_wear(usages: &[Usage], config_items: &HashMap<String,f32>, material: Option<&str>, ledger: Option<&Path>, program: &Path) -> Outcome {
+    let name = "Tool Wear";
+    let mut issues: Vec<Issue> = Vec::new();
+    let mut details: Vec<String> = Vec::new();
+    let program = program.display().to_string();
+    //what other programs have used each serial, and what this one uses of it
+    let others: Vec<(u32,String,f32,f32)> = ledger.map(|l| {
+        let _lock = LEDGER.lock().unwrap();
+        read_ledger(l)
+    }).unwrap_or_default().into_iter().filter(|e| e.1 != program).collect();
+    let totals = by_serial(usages, config_items, material);
+
+    for usage in usages {
+        let q = config::Qualifiers {
+            material,
+            tool_number: usage.tool_number,
+            diameter: usage.diameter,
+        };
+        let life = config::lookup(config_items, "TOOL_LIFE", &q);
+        let serial = serial(usage, config_items, material);
+        details.push(format!("{}: {:.1} in cut, {:.2} cubic in removed",usage.tool,usage.distance,usage.volume));
+
+        let mut used = usage.distance;
+        if let (Some(_), true) = (ledger, serial > 0) {
+            let this = totals.iter().find(|t| t.0 == serial).map_or(0.0, |t| t.1);
+            used = this + others.iter().filter(|e| e.0 == serial).map(|e| e.2).sum::<f32>();
+            details.push(format!("bit #{}: {:.0} of {} in used",serial,used,life));
+        }
+        if used > life {
+            issues.push(Issue::new(Status::Warning, "past_life",
+                format!("{}{} is past its tool life: {:.0} in cut, life is {} in",
+                    usage.tool,
+                    if serial > 0 { format!(" (bit #{})",serial) } else { String::new() },
+                    used,life,
+                )
+            ));
+        }
+    }
+
+    if usages.is_empty() {
+        return Outcome::skipped(name, Reason::NoCuttingMoves);
+    }
+    Outcome::from_issues(name, issues, "no tool is past its tool life".into())
+        .with_details(details.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(tool_number: u32, distance: f32) -> Usage {
+        Usage { tool: format!("T{}",tool_number), tool_number: Some(tool_number), diameter: Some(0.25), distance, volume: 0.0 }
+    }
+
+    /// both tool slots hold bit #7, which is good for 100 inches
+    fn config_items() -> HashMap<String,f32> {
+        HashMap::from([
+            ("TOOL_LIFE".into(), 100.0),
+            ("TOOL_SERIAL".into(), 0.0),
+            ("TOOL_SERIAL[T1]".into(), 7.0),
+            ("TOOL_SERIAL[T2]".into(), 7.0),
+        ])
+    }
+
+    fn ledger(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("validate_toolpath_{}_{}.tsv",name,std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn tools_sharing_a_serial_are_added_up() {
+        let path = ledger("shared");
+        let usages = [usage(1, 40.0), usage(2, 30.0)];
+        record(&usages, &config_items(), None, &path, Path::new("a.nc")).unwrap();
+        assert_eq!(read_ledger(&path), vec![(7, "a.nc".into(), 70.0, 0.0)]);
+
+        //a second program pushes the bit past its life, and checking the first again doesn't count it twice
+        record(&[usage(1, 50.0)], &config_items(), None, &path, Path::new("b.nc")).unwrap();
+        record(&usages, &config_items(), None, &path, Path::new("a.nc")).unwrap();
+        let outcome = check_wear(&[usage(1, 50.0)], &config_items(), None, Some(&path), Path::new("b.nc"));
+        assert_eq!(outcome.status, Status::Warning);
+        assert!(outcome.message.contains("120 in cut"), "{}", outcome.message);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn checking_wear_leaves_the_ledger_alone() {
+        let path = ledger("untouched");
+        let outcome = check_wear(&[usage(1, 40.0)], &config_items(), None, Some(&path), Path::new("a.nc"));
+        assert_eq!(outcome.status, Status::Pass);
+        assert!(!path.exists());
+    }
+}